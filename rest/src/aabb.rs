@@ -26,7 +26,12 @@ impl AABB {
         AABB { min, max }
     }
 
-    pub fn hit(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.interval(ray, t_min, t_max).is_some()
+    }
+
+    // The part of [t_min, t_max] during which the ray is inside the box.
+    pub fn interval(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
         for a in 0..3 {
             let inv_d = 1.0 / ray.direction()[a];
             let t0 = (self.min[a] - ray.origin()[a]) * inv_d;
@@ -35,9 +40,9 @@ impl AABB {
             t_min = t_min.max(t0);
             t_max = t_max.min(t1);
             if t_max <= t_min {
-                return false;
+                return None;
             }
         }
-        true
+        Some((t_min, t_max))
     }
 }
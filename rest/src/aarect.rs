@@ -2,6 +2,7 @@ use crate::aabb::AABB;
 use crate::hittable::*;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::sampler;
 use crate::vec3::*;
 use rand::Rng;
 
@@ -73,15 +74,15 @@ impl<M: Material> Hittable for AARect<M> {
                 Plane::XY => Vec3::new(0.0, 0.0, 1.0),
             };
 
-            Some(HitRecord::new(
-                p,
-                &self.material,
-                t,
-                u,
-                v,
-                ray,
-                outward_normal,
-            ))
+            let mut dpdu = Vec3::default();
+            let mut dpdv = Vec3::default();
+            dpdu[a_axis] = self.a1 - self.a0;
+            dpdv[b_axis] = self.b1 - self.b0;
+
+            Some(
+                HitRecord::new(p, &self.material, t, u, v, ray, outward_normal)
+                    .with_tangents(dpdu, dpdv),
+            )
         }
     }
 
@@ -107,7 +108,7 @@ impl<M: Material> Hittable for AARect<M> {
     }
 
     fn random(&self, o: &Vec3) -> Vec3 {
-        let mut rng = rand::thread_rng();
+        let mut rng = sampler::rng();
         let (k_axis, a_axis, b_axis) = get_axis(&self.plane);
         let mut random_point = Vec3::default();
         random_point[a_axis] = rng.gen_range(self.a0..=self.a1);
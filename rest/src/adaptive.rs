@@ -0,0 +1,47 @@
+use crate::color::luminance;
+use crate::vec3::Color;
+
+// Running mean and variance (Welford) of a pixel's sample luminances, to
+// stop sampling once the pixel has converged.
+#[derive(Default)]
+pub struct PixelStats {
+    samples: i32,
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelStats {
+    pub fn add(&mut self, sample: &Color) {
+        let x = luminance(sample);
+        // Leave broken samples to the NaN handling in `get_color`.
+        let x = if x.is_finite() { x } else { 0.0 };
+        self.samples += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.samples as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    pub fn samples(&self) -> i32 {
+        self.samples
+    }
+
+    // True once the standard error of the mean is within `threshold` of the
+    // mean, after at least `min_samples`. The floor on the mean keeps black
+    // pixels from needing exact agreement.
+    pub fn converged(&self, threshold: f64, min_samples: i32) -> bool {
+        if self.samples < min_samples.max(2) {
+            return false;
+        }
+        let n = self.samples as f64;
+        let variance = self.m2 / (n - 1.0);
+        (variance / n).sqrt() <= threshold * self.mean.max(1e-3)
+    }
+}
+
+// Colour for a pixel that took `samples` of at most `max_samples`: black
+// through red and yellow to white.
+pub fn heatmap_color(samples: i32, max_samples: i32) -> (i32, i32, i32) {
+    let t = (samples as f64 / max_samples.max(1) as f64).clamp(0.0, 1.0);
+    let channel = |start: f64| ((3.0 * t - start).clamp(0.0, 1.0) * 255.0) as i32;
+    (channel(0.0), channel(1.0), channel(2.0))
+}
@@ -0,0 +1,42 @@
+use crate::aov::AovSample;
+use crate::hittable::*;
+use crate::integrator::*;
+use crate::onb::ONB;
+use crate::pdf::random_cosine_direction;
+use crate::ray::Ray;
+use crate::rtweekend::INFINITY;
+use crate::vec3::*;
+
+// Shades the first hit by how open the hemisphere above it is: the fraction
+// of `samples` cosine-weighted rays that get `distance` away without hitting
+// anything. Ignores materials and lights entirely, for checking geometry.
+pub struct AmbientOcclusion<'a> {
+    pub world: &'a dyn Hittable,
+    pub background: Color,
+    pub distance: f64,
+    pub samples: u32,
+}
+
+impl<'a> Integrator for AmbientOcclusion<'a> {
+    fn ray_color(&self, r: &Ray) -> (Color, AovSample, Option<i32>) {
+        let rec = match self.world.hit(r, 0.001, INFINITY) {
+            Some(rec) => rec,
+            None => return background_sample(&self.background),
+        };
+        let mut aov = surface_aovs(r, &rec);
+        // The geometric normal already faces the camera side.
+        let uvw = ONB::build_from_w(&rec.normal);
+        let open = (0..self.samples)
+            .filter(|_| {
+                let dir = unit_vector(&uvw.local(&random_cosine_direction()));
+                let ray = Ray::new(rec.p, dir, r.time());
+                self.world.hit(&ray, 0.001, self.distance).is_none()
+            })
+            .count();
+        let visibility = open as f64 / self.samples.max(1) as f64;
+        // Goes in the direct pass so the denoiser treats it like lighting.
+        aov.emission = Color::default();
+        aov.direct = Color::new(visibility, visibility, visibility);
+        (aov.direct, aov, None)
+    }
+}
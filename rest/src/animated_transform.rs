@@ -0,0 +1,202 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::quaternion::Quaternion;
+use crate::ray::Ray;
+use crate::vec3::*;
+
+// Scale, then rotate, then translate, at a point in time.
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    #[allow(dead_code)]
+    pub fn new(time: f64, translation: Vec3, rotation: Quaternion, scale: Vec3) -> Self {
+        Keyframe {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    fn lerp(&self, other: &Keyframe, time: f64) -> Keyframe {
+        let t = (time - self.time) / (other.time - self.time);
+        Keyframe {
+            time,
+            translation: lerp(&self.translation, &other.translation, t),
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: lerp(&self.scale, &other.scale, t),
+        }
+    }
+
+    pub fn point_to_world(&self, p: &Vec3) -> Vec3 {
+        self.rotation.rotate(&(p * &self.scale)) + self.translation
+    }
+
+    pub fn vector_to_world(&self, v: &Vec3) -> Vec3 {
+        self.rotation.rotate(&(v * &self.scale))
+    }
+
+    pub fn normal_to_world(&self, n: &Vec3) -> Vec3 {
+        unit_vector(&self.rotation.rotate(&(n / &self.scale)))
+    }
+
+    pub fn point_to_object(&self, p: &Vec3) -> Vec3 {
+        &self.rotation.conjugate().rotate(&(p - &self.translation)) / &self.scale
+    }
+
+    pub fn vector_to_object(&self, v: &Vec3) -> Vec3 {
+        &self.rotation.conjugate().rotate(v) / &self.scale
+    }
+}
+
+// Keyframes sorted by time, interpolated linearly (slerp for rotation) and
+// held constant outside the first and last keyframe.
+#[derive(Clone)]
+pub struct KeyframeTrack {
+    keyframes: Vec<Keyframe>,
+}
+
+impl KeyframeTrack {
+    #[allow(dead_code)]
+    pub fn new(mut keyframes: Vec<Keyframe>) -> Self {
+        if keyframes.is_empty() {
+            panic!["keyframe track needs at least one keyframe"]
+        }
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        KeyframeTrack { keyframes }
+    }
+
+    pub fn at(&self, time: f64) -> Keyframe {
+        let first = &self.keyframes[0];
+        if time <= first.time {
+            return *first;
+        }
+        for pair in self.keyframes.windows(2) {
+            if time <= pair[1].time {
+                return pair[0].lerp(&pair[1], time);
+            }
+        }
+        self.keyframes[self.keyframes.len() - 1]
+    }
+
+    // Keyframe times strictly inside (t0, t1).
+    fn times_between(&self, t0: f64, t1: f64) -> impl Iterator<Item = f64> + '_ {
+        self.keyframes
+            .iter()
+            .map(|k| k.time)
+            .filter(move |&t| t > t0 && t < t1)
+    }
+}
+
+// Applies a time-varying transform to any hittable, so boxes can move and
+// objects can spin during the shutter interval.
+pub struct AnimatedTransform<H: Hittable> {
+    hittable: H,
+    track: KeyframeTrack,
+}
+
+impl<H: Hittable> AnimatedTransform<H> {
+    #[allow(dead_code)]
+    pub fn new(hittable: H, track: KeyframeTrack) -> Self {
+        AnimatedTransform { hittable, track }
+    }
+}
+
+// Light sampling takes no time, so animated lights are sampled where they sit
+// at time 0, the same time `surface_pdf_value` evaluates shapes at.
+// `validate_lights` fails lights that move during the shutter.
+const SAMPLING_TIME: f64 = 0.0;
+
+impl<H: Hittable> Hittable for AnimatedTransform<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let xf = self.track.at(ray.time());
+        let local_ray = Ray::new(
+            xf.point_to_object(ray.origin()),
+            xf.vector_to_object(ray.direction()),
+            ray.time(),
+        );
+        self.hittable.hit(&local_ray, t_min, t_max).map(|mut hit| {
+            hit.p = xf.point_to_world(&hit.p);
+            hit.normal = xf.normal_to_world(&hit.normal);
+            hit.shading_normal = xf.normal_to_world(&hit.shading_normal);
+            hit.dpdu = xf.vector_to_world(&hit.dpdu);
+            hit.dpdv = xf.vector_to_world(&hit.dpdv);
+            hit
+        })
+    }
+
+    // Scaling squeezes directions as it maps them, so the child's density is
+    // rescaled by the Jacobian |det A| / |A v|^3 of the world-to-object map A.
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        let xf = self.track.at(SAMPLING_TIME);
+        let local_v = xf.vector_to_object(&unit_vector(v));
+        let det = 1.0 / (xf.scale.x() * xf.scale.y() * xf.scale.z()).abs();
+        self.hittable.pdf_value(&xf.point_to_object(o), &local_v) * det / local_v.length().powi(3)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        let xf = self.track.at(SAMPLING_TIME);
+        xf.vector_to_world(&self.hittable.random(&xf.point_to_object(o)))
+    }
+
+    fn sampling_tolerance(&self) -> f64 {
+        self.hittable.sampling_tolerance()
+    }
+
+    // Unions the transformed box at evenly spaced times and at every keyframe
+    // in between, padded by how far a corner can bow out while rotating
+    // between two samples.
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        const STEPS: usize = 64;
+        let local = self.hittable.bounding_box(t0, t1)?;
+        let mut times: Vec<f64> = (0..=STEPS)
+            .map(|i| t0 + (t1 - t0) * i as f64 / STEPS as f64)
+            .chain(self.track.times_between(t0, t1))
+            .collect();
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let bounds = [local.min, local.max];
+        let corners: Vec<Vec3> = (0..8)
+            .map(|i| {
+                Vec3::new(
+                    bounds[i & 1].x(),
+                    bounds[(i >> 1) & 1].y(),
+                    bounds[(i >> 2) & 1].z(),
+                )
+            })
+            .collect();
+
+        let mut bbox: Option<AABB> = None;
+        let mut prev: Option<Keyframe> = None;
+        for time in times {
+            let xf = self.track.at(time);
+            let mut min = Vec3::new(f64::MAX, f64::MAX, f64::MAX);
+            let mut max = Vec3::new(f64::MIN, f64::MIN, f64::MIN);
+            let mut radius: f64 = 0.0;
+            for c in corners.iter() {
+                let p = xf.point_to_world(c);
+                radius = radius.max((c * &xf.scale).length());
+                for a in 0..3 {
+                    min[a] = min[a].min(p[a]);
+                    max[a] = max[a].max(p[a]);
+                }
+            }
+            let angle = prev.map_or(0.0, |p| p.rotation.angle_to(&xf.rotation));
+            let pad = radius * (1.0 - (0.5 * angle).cos());
+            let pad = Vec3::new(pad, pad, pad);
+            let step = AABB::new(min - pad, max + pad);
+            bbox = Some(match bbox {
+                Some(b) => surrounding_box(&b, &step),
+                None => step,
+            });
+            prev = Some(xf);
+        }
+        bbox
+    }
+}
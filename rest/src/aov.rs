@@ -0,0 +1,205 @@
+use crate::vec3::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// What the first surface hit by one camera sample looked like. The lighting
+// is split into the surface's own emission, light that reached it straight
+// from an emitter (or the background), and everything that bounced more.
+#[derive(Clone, Copy, Default)]
+pub struct AovSample {
+    pub albedo: Color,
+    pub normal: Vec3,
+    // Distance from the camera.
+    pub depth: f64,
+    pub position: Point3,
+    pub uv: (f64, f64),
+    // Both IDs are `None` where the ray escaped. The object ID is only the
+    // index in the outermost `HittableList`, so everything inside one BVH or
+    // transform shares it; the material ID comes from `Material::id`.
+    pub object_id: Option<usize>,
+    pub material_id: Option<usize>,
+    pub emission: Color,
+    pub direct: Color,
+    pub indirect: Color,
+}
+
+// Running sums over a pixel's samples. Surface passes are averaged over the
+// samples that hit something; IDs come from the first of those.
+#[derive(Clone, Default)]
+pub struct AovPixel {
+    samples: i32,
+    hits: u32,
+    albedo: Color,
+    normal: Vec3,
+    depth: f64,
+    position: Point3,
+    uv: (f64, f64),
+    object_id: Option<usize>,
+    material_id: Option<usize>,
+    emission: Color,
+    direct: Color,
+    indirect: Color,
+}
+
+impl AovPixel {
+    // `weight` scales the lighting the same way the camera weights the
+    // beauty sample.
+    pub fn add(&mut self, sample: &AovSample, weight: f64) {
+        self.samples += 1;
+        self.emission += weight * sample.emission;
+        self.direct += weight * sample.direct;
+        self.indirect += weight * sample.indirect;
+        if sample.object_id.is_none() {
+            return;
+        }
+        self.hits += 1;
+        self.albedo += sample.albedo;
+        self.normal += sample.normal;
+        self.depth += sample.depth;
+        self.position += sample.position;
+        self.uv.0 += sample.uv.0;
+        self.uv.1 += sample.uv.1;
+        if self.object_id.is_none() {
+            self.object_id = sample.object_id;
+            self.material_id = sample.material_id;
+        }
+    }
+
+    // The pixel's AOVs: surface passes averaged over hits, lighting over all
+    // samples.
+    fn mean(&self) -> AovSample {
+        let spp = self.samples.max(1) as f64;
+        let hits = self.hits.max(1) as f64;
+        AovSample {
+            albedo: self.albedo / hits,
+            normal: self.normal / hits,
+            depth: self.depth / hits,
+            position: self.position / hits,
+            uv: (self.uv.0 / hits, self.uv.1 / hits),
+            object_id: self.object_id,
+            material_id: self.material_id,
+            emission: self.emission / spp,
+            direct: self.direct / spp,
+            indirect: self.indirect / spp,
+        }
+    }
+}
+
+// All AOVs of a frame, rows from top to bottom like the beauty image.
+pub struct AovBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<AovPixel>,
+}
+
+// `image.ppm` becomes `image_albedo.pfm` for the albedo pass.
+pub fn aov_path(output: &str, name: &str, ext: &str) -> String {
+    let stem = output.rsplit_once('.').map_or(output, |(stem, _)| stem);
+    format!("{}_{}.{}", stem, name, ext)
+}
+
+// Portable float map: linear RGB, little-endian, rows from bottom to top.
+fn write_pfm(path: &str, width: usize, height: usize, pixels: &[Vec3]) {
+    let mut file = BufWriter::new(File::create(path).expect("Unable to create file"));
+    let header = format!("PF\n{} {}\n-1.0\n", width, height);
+    file.write_all(header.as_bytes())
+        .expect("Unable to write data");
+    for row in pixels.chunks(width).rev() {
+        for p in row {
+            for a in 0..3 {
+                file.write_all(&(p[a] as f32).to_le_bytes())
+                    .expect("Unable to write data");
+            }
+        }
+    }
+    file.flush().unwrap();
+}
+
+// Renumbers IDs in order of first appearance and writes each as a distinct
+// colour, black where nothing was hit.
+fn write_ids(path: &str, width: usize, height: usize, ids: &[Option<usize>]) {
+    let mut index = HashMap::new();
+    let mut file = BufWriter::new(File::create(path).expect("Unable to create file"));
+    let header = format!("P3\n{} {}\n255\n", width, height);
+    file.write_all(header.as_bytes())
+        .expect("Unable to write data");
+    for id in ids {
+        let (r, g, b) = match id {
+            Some(id) => {
+                let next = index.len() + 1;
+                let n = *index.entry(*id).or_insert(next) as u32;
+                // Scramble neighbouring IDs into unrelated colours.
+                let h = n.wrapping_mul(0x9e37_79b9) ^ (n >> 16);
+                ((h >> 24) & 0xff, (h >> 16) & 0xff, (h >> 8) & 0xff)
+            }
+            None => (0, 0, 0),
+        };
+        file.write_all(format!("{} {} {}\n", r, g, b).as_bytes())
+            .expect("Unable to write data");
+    }
+    file.flush().unwrap();
+}
+
+impl AovBuffer {
+    pub fn new(width: usize, height: usize, pixels: Vec<AovPixel>) -> Self {
+        if pixels.len() != width * height {
+            panic![
+                "AOV buffer needs {} pixels, got {}",
+                width * height,
+                pixels.len()
+            ]
+        }
+        AovBuffer {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Per-pixel AOVs, rows from top to bottom.
+    pub fn resolve(&self) -> Vec<AovSample> {
+        self.pixels.iter().map(|p| p.mean()).collect()
+    }
+
+    // Writes every pass next to `output`: float passes as PFM, IDs as PPM.
+    pub fn write(&self, output: &str) {
+        let pixels = self.resolve();
+        let pass = |name: &str, value: &dyn Fn(&AovSample) -> Vec3| {
+            let values: Vec<Vec3> = pixels.iter().map(value).collect();
+            write_pfm(
+                &aov_path(output, name, "pfm"),
+                self.width,
+                self.height,
+                &values,
+            );
+        };
+        pass("albedo", &|p| p.albedo);
+        pass("normal", &|p| p.normal);
+        pass("depth", &|p| Vec3::new(p.depth, p.depth, p.depth));
+        pass("position", &|p| p.position);
+        pass("uv", &|p| Vec3::new(p.uv.0, p.uv.1, 0.0));
+        pass("emission", &|p| p.emission);
+        pass("direct", &|p| p.direct);
+        pass("indirect", &|p| p.indirect);
+        let ids = |name: &str, id: &dyn Fn(&AovSample) -> Option<usize>| {
+            let values: Vec<Option<usize>> = pixels.iter().map(id).collect();
+            write_ids(
+                &aov_path(output, name, "ppm"),
+                self.width,
+                self.height,
+                &values,
+            );
+        };
+        ids("object_id", &|p| p.object_id);
+        ids("material_id", &|p| p.material_id);
+    }
+}
@@ -0,0 +1,507 @@
+use crate::camera::CameraModel;
+use crate::hittable::*;
+use crate::light_sampler::*;
+use crate::material::*;
+use crate::ray::Ray;
+use crate::rtweekend::INFINITY;
+use crate::vec3::*;
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+#[derive(Clone)]
+struct Vertex<'a> {
+    kind: VertexKind,
+    p: Point3,
+    // Geometric normal; unused at the camera, which isn't on a surface.
+    n: Vec3,
+    rec: Option<HitRecord<'a>>,
+    // Unit direction back toward the previous vertex of the subpath.
+    wo: Vec3,
+    // Throughput from the start of the subpath up to this vertex.
+    beta: Color,
+    // Scattered specularly, so it can't be connected to another vertex.
+    delta: bool,
+    // Area densities of sampling this vertex from the previous vertex of its
+    // own subpath, and from the next one walking the other way.
+    pdf_fwd: f64,
+    pdf_rev: f64,
+    // Light vertices: whether the front and back of the surface emit.
+    emits: [bool; 2],
+}
+
+impl<'a> Vertex<'a> {
+    fn new(kind: VertexKind, p: Point3, n: Vec3, beta: Color) -> Self {
+        Vertex {
+            kind,
+            p,
+            n,
+            rec: None,
+            wo: Vec3::default(),
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            emits: [false; 2],
+        }
+    }
+
+    fn on_surface(&self) -> bool {
+        self.kind != VertexKind::Camera
+    }
+}
+
+// Turns a solid-angle density of leaving `from` toward `to` into an area
+// density at `to`.
+fn to_area(pdf: f64, from: &Vertex, to: &Vertex) -> f64 {
+    let d = to.p - from.p;
+    let dist2 = d.length_squared();
+    if dist2 == 0.0 {
+        return 0.0;
+    }
+    let mut pdf = pdf / dist2;
+    if to.on_surface() {
+        pdf *= dot(&to.n, &d).abs() / dist2.sqrt();
+    }
+    pdf
+}
+
+// Bidirectional path tracer. Every camera sample also traces a subpath from
+// the lights, and all pairs of vertices are connected, weighted by the
+// balance heuristic. Connections straight to the camera (light tracing) land
+// elsewhere on the image and are splatted onto the film.
+// Lights the sampler can't pick a point on are still found by camera
+// subpaths.
+pub struct Bdpt<'a> {
+    pub world: &'a dyn Hittable,
+    pub lights: LightSampler<'a>,
+    pub camera: &'a dyn CameraModel,
+    pub background: Color,
+    pub max_depth: i32,
+    // Image size and crop window [x0, x1) by [y0, y1), y running down, for
+    // mapping camera (s, t) to pixels.
+    pub image_width: usize,
+    pub image_height: usize,
+    pub crop: (usize, usize, usize, usize),
+}
+
+impl<'a> Bdpt<'a> {
+    // Radiance along the camera ray `r`. Light-tracing contributions go to
+    // `splat` at image position (x, y), y running down, instead.
+    pub fn sample(&self, r: &Ray, splat: &mut impl FnMut(f64, f64, Color)) -> Color {
+        let time = r.time();
+        let max_depth = self.max_depth.max(0) as usize;
+
+        let mut camera = Vec::with_capacity(max_depth + 2);
+        let mut cam = Vertex::new(
+            VertexKind::Camera,
+            *r.origin(),
+            Vec3::default(),
+            Color::new(1.0, 1.0, 1.0),
+        );
+        let pdf_dir = match self.camera_pdf(r.origin(), &(r.origin() + r.direction())) {
+            Some(pdf) => pdf,
+            None => {
+                // Can't be reached by light tracing; keep MIS off that strategy.
+                cam.delta = true;
+                1.0
+            }
+        };
+        camera.push(cam);
+        let beta = camera[0].beta;
+        let escaped = self.random_walk(
+            Ray::new(*r.origin(), *r.direction(), time),
+            beta,
+            pdf_dir,
+            max_depth + 1,
+            &mut camera,
+        );
+        let mut l = &escaped * &self.background;
+
+        let mut light = Vec::with_capacity(max_depth + 1);
+        if let Some(point) = self.lights.sample(time) {
+            if let Some((ray, beta, pdf_dir)) = self.lights.sample_ray(&point, time) {
+                light.push(self.light_vertex(&point));
+                self.random_walk(ray, beta, pdf_dir, max_depth, &mut light);
+            }
+        }
+
+        for t in 1..=camera.len() {
+            // Next event estimation (s = 1) samples its own light point, so it
+            // stays available even when no light subpath could be started.
+            for s in 0..=light.len().max(1) {
+                let depth = s as i64 + t as i64 - 2;
+                if (s == 1 && t == 1) || depth < 0 || depth > max_depth as i64 {
+                    continue;
+                }
+                if t == 1 {
+                    if let Some((x, y, c)) = self.connect_camera(&mut light, &mut camera, s, time) {
+                        splat(x, y, c);
+                    }
+                } else {
+                    l += self.connect(&mut light, &mut camera, s, t, time);
+                }
+            }
+        }
+        l
+    }
+
+    // Extends `path` from its last vertex along `ray`, sampled with
+    // solid-angle density `pdf_dir` and carrying throughput `beta`, by up to
+    // `max_vertices` vertices. Returns the throughput of a ray that escaped
+    // the scene.
+    fn random_walk(
+        &self,
+        mut ray: Ray,
+        mut beta: Color,
+        mut pdf_dir: f64,
+        max_vertices: usize,
+        path: &mut Vec<Vertex<'a>>,
+    ) -> Color {
+        let mut added = 0;
+        while added < max_vertices {
+            let rec = match self.world.hit(&ray, 0.001, INFINITY) {
+                Some(rec) => rec,
+                None => return beta,
+            };
+            let wo = unit_vector(&-ray.direction());
+            let prev = path.len() - 1;
+            let mut v = Vertex::new(VertexKind::Surface, rec.p, rec.normal, beta);
+            v.wo = wo;
+            v.pdf_fwd = to_area(pdf_dir, &path[prev], &v);
+            let material = rec.material;
+            v.rec = Some(rec.clone());
+            path.push(v);
+            added += 1;
+            if added >= max_vertices {
+                break;
+            }
+
+            let pdf_rev = match material.scatter(&ray, &rec) {
+                None => break,
+                Some(ScatterRecord::Specular {
+                    specular_ray,
+                    attenuation,
+                }) => {
+                    beta = &beta * &attenuation;
+                    ray = specular_ray;
+                    pdf_dir = 0.0;
+                    path[prev + 1].delta = true;
+                    0.0
+                }
+                Some(ScatterRecord::Scatter { pdf, attenuation }) => {
+                    let dir = unit_vector(&pdf.generate());
+                    pdf_dir = pdf.value(&dir);
+                    if pdf_dir <= 0.0 {
+                        break;
+                    }
+                    let scattered = Ray::new(rec.p, dir, ray.time());
+                    let f_cos = attenuation * material.scattering_pdf(&ray, &rec, &scattered);
+                    beta = &beta * &(f_cos / pdf_dir);
+                    ray = scattered;
+                    pdf.value(&wo)
+                }
+            };
+            if is_black(&beta) {
+                break;
+            }
+            let rev = to_area(pdf_rev, &path[prev + 1], &path[prev]);
+            path[prev].pdf_rev = rev;
+        }
+        Color::default()
+    }
+
+    // A light vertex, with `beta` holding 1 / area density.
+    fn sample_light(&self, time: f64) -> Option<Vertex<'a>> {
+        let point = self.lights.sample(time)?;
+        Some(self.light_vertex(&point))
+    }
+
+    fn light_vertex(&self, point: &LightPoint) -> Vertex<'a> {
+        let mut v = Vertex::new(
+            VertexKind::Light,
+            point.p,
+            point.n,
+            Color::new(1.0, 1.0, 1.0) / point.pdf,
+        );
+        v.pdf_fwd = point.pdf;
+        v.emits = point.emits;
+        v
+    }
+
+    // Solid-angle density of the camera sampling a ray from `origin` toward
+    // `p` over the crop window, or `None` if the camera can't say.
+    fn camera_pdf(&self, origin: &Point3, p: &Point3) -> Option<f64> {
+        let ip = self.camera.project(p)?;
+        if (ip.lens - *origin).length_squared() > 1e-12 {
+            return None;
+        }
+        if self.raster(ip.s, ip.t).is_none() {
+            return Some(0.0);
+        }
+        let (x0, x1, y0, y1) = self.crop;
+        let pixels = ((x1 - x0) * (y1 - y0)) as f64;
+        // Samples are uniform over the crop's pixels, and a pixel spans
+        // 1 / (width - 1) by 1 / (height - 1) of (s, t).
+        let pdf_st = (self.image_width as f64 - 1.0) * (self.image_height as f64 - 1.0) / pixels;
+        Some(pdf_st / ip.solid_angle)
+    }
+
+    // Image position of camera coordinates (s, t), y running down, if it
+    // falls inside the crop window.
+    fn raster(&self, s: f64, t: f64) -> Option<(f64, f64)> {
+        let x = s * (self.image_width as f64 - 1.0);
+        let y = self.image_height as f64 - t * (self.image_height as f64 - 1.0);
+        let (x0, x1, y0, y1) = self.crop;
+        if x < x0 as f64 || x >= x1 as f64 || y < y0 as f64 || y >= y1 as f64 {
+            return None;
+        }
+        Some((x, y))
+    }
+
+    // Material at surface vertex `v` times the cosine, for light arriving
+    // along `v.wo` and leaving toward `w`, or the reverse.
+    fn f_cos(&self, v: &Vertex, w: &Vec3, time: f64) -> Color {
+        match v.kind {
+            VertexKind::Light => self.lights.le(&v.p, w, time) * dot(&unit_vector(w), &v.n).abs(),
+            VertexKind::Camera => Color::default(),
+            VertexKind::Surface => {
+                let rec = v.rec.as_ref().unwrap();
+                let r_in = Ray::new(v.p + v.wo, -v.wo, time);
+                match rec.material.scatter(&r_in, rec) {
+                    Some(ScatterRecord::Scatter { attenuation, .. }) => {
+                        let scattered = Ray::new(v.p, *w, time);
+                        attenuation * rec.material.scattering_pdf(&r_in, rec, &scattered)
+                    }
+                    _ => Color::default(),
+                }
+            }
+        }
+    }
+
+    // Area density at `next` of `v` sampling it, having been reached from
+    // `prev`.
+    fn pdf(&self, v: &Vertex, prev: Option<&Vertex>, next: &Vertex, time: f64) -> f64 {
+        let pdf_dir = match v.kind {
+            VertexKind::Light => return self.pdf_light(v, next),
+            VertexKind::Camera => self.camera_pdf(&v.p, &next.p).unwrap_or(0.0),
+            VertexKind::Surface => {
+                let wo = match prev {
+                    Some(prev) => unit_vector(&(prev.p - v.p)),
+                    None => v.wo,
+                };
+                let rec = v.rec.as_ref().unwrap();
+                let r_in = Ray::new(v.p + wo, -wo, time);
+                match rec.material.scatter(&r_in, rec) {
+                    Some(ScatterRecord::Scatter { pdf, .. }) => {
+                        pdf.value(&unit_vector(&(next.p - v.p)))
+                    }
+                    _ => 0.0,
+                }
+            }
+        };
+        to_area(pdf_dir, v, next)
+    }
+
+    // Area density at `next` of light vertex `v` emitting toward it.
+    fn pdf_light(&self, v: &Vertex, next: &Vertex) -> f64 {
+        let point = LightPoint {
+            p: v.p,
+            n: v.n,
+            pdf: v.pdf_fwd,
+            emits: v.emits,
+        };
+        to_area(point.pdf_dir(&(next.p - v.p)), v, next)
+    }
+
+    fn visible(&self, a: &Point3, b: &Point3, time: f64) -> bool {
+        let d = b - a;
+        let dist = d.length();
+        let ray = Ray::new(*a, d / dist, time);
+        self.world.hit(&ray, 0.001, dist - 0.001).is_none()
+    }
+
+    // Strategy with `s` light and `t` camera vertices, t >= 2.
+    fn connect(
+        &self,
+        light: &mut Vec<Vertex<'a>>,
+        camera: &mut [Vertex<'a>],
+        s: usize,
+        t: usize,
+        time: f64,
+    ) -> Color {
+        let pt = &camera[t - 1];
+        if s == 0 {
+            // The camera subpath found a light on its own.
+            let rec = match &pt.rec {
+                Some(rec) => rec,
+                None => return Color::default(),
+            };
+            let r_in = Ray::new(pt.p + pt.wo, -pt.wo, time);
+            let le = rec.material.emitted(&r_in, rec);
+            if is_black(&le) {
+                return Color::default();
+            }
+            let l = &pt.beta * &le;
+            camera[t - 1].emits =
+                self.lights
+                    .emitting_sides(&camera[t - 1].p, &camera[t - 1].n, time);
+            return l * self.mis_weight(light, camera, s, t, time);
+        }
+        if pt.delta {
+            return Color::default();
+        }
+        let saved = if s == 1 {
+            // Next event estimation takes a fresh point on the lights.
+            let sampled = match self.sample_light(time) {
+                Some(v) => v,
+                None => return Color::default(),
+            };
+            if light.is_empty() {
+                light.push(sampled);
+                None
+            } else {
+                Some(std::mem::replace(&mut light[0], sampled))
+            }
+        } else {
+            None
+        };
+        let qs = &light[s - 1];
+        let pt = &camera[t - 1];
+        let mut l = Color::default();
+        if !qs.delta {
+            let d = pt.p - qs.p;
+            let f = &self.f_cos(qs, &d, time) * &self.f_cos(pt, &-d, time);
+            if !is_black(&f) && self.visible(&qs.p, &pt.p, time) {
+                l = &(&qs.beta * &f) * &pt.beta / d.length_squared();
+                l *= self.mis_weight(light, camera, s, t, time);
+            }
+        }
+        match saved {
+            Some(v) => light[0] = v,
+            None if s == 1 => {
+                light.pop();
+            }
+            None => {}
+        }
+        l
+    }
+
+    // Strategy connecting light vertex `s - 1` straight to the camera.
+    // Returns the image position and contribution to splat there.
+    fn connect_camera(
+        &self,
+        light: &mut [Vertex<'a>],
+        camera: &mut [Vertex<'a>],
+        s: usize,
+        time: f64,
+    ) -> Option<(f64, f64, Color)> {
+        let qs = &light[s - 1];
+        if qs.delta || camera[0].delta {
+            return None;
+        }
+        let ip = self.camera.project(&qs.p)?;
+        let (x, y) = self.raster(ip.s, ip.t)?;
+        let d = ip.lens - qs.p;
+        let f = self.f_cos(qs, &d, time);
+        if is_black(&f) || !self.visible(&qs.p, &ip.lens, time) {
+            return None;
+        }
+        let pdf_dir = self.camera_pdf(&ip.lens, &qs.p)?;
+        let l = &qs.beta * &f * (pdf_dir / d.length_squared());
+        let weight = self.mis_weight(light, camera, s, 1, time);
+        Some((x, y, l * weight))
+    }
+
+    // Balance heuristic weight of the strategy with `s` light and `t` camera
+    // vertices among all strategies that could have made the same path.
+    fn mis_weight(
+        &self,
+        light: &mut [Vertex<'a>],
+        camera: &mut [Vertex<'a>],
+        s: usize,
+        t: usize,
+        time: f64,
+    ) -> f64 {
+        if s + t == 2 {
+            return 1.0;
+        }
+        // Densities of the connection vertices as if the other subpath had
+        // sampled them.
+        let pt_rev = if s > 0 {
+            let qs_prev = if s > 1 { Some(&light[s - 2]) } else { None };
+            self.pdf(&light[s - 1], qs_prev, &camera[t - 1], time)
+        } else {
+            self.lights.pdf(&camera[t - 1].p, time)
+        };
+        let pt_minus_rev = if t < 2 {
+            None
+        } else if s > 0 {
+            Some(self.pdf(&camera[t - 1], Some(&light[s - 1]), &camera[t - 2], time))
+        } else {
+            let mut pt = camera[t - 1].clone();
+            pt.kind = VertexKind::Light;
+            Some(self.pdf_light(&pt, &camera[t - 2]))
+        };
+        let qs_rev = if s > 0 {
+            let pt_prev = if t > 1 { Some(&camera[t - 2]) } else { None };
+            Some(self.pdf(&camera[t - 1], pt_prev, &light[s - 1], time))
+        } else {
+            None
+        };
+        let qs_minus_rev = if s > 1 {
+            Some(self.pdf(&light[s - 1], Some(&camera[t - 1]), &light[s - 2], time))
+        } else {
+            None
+        };
+
+        let saved_camera: Vec<(f64, bool)> = camera.iter().map(|v| (v.pdf_rev, v.delta)).collect();
+        let saved_light: Vec<(f64, bool)> = light.iter().map(|v| (v.pdf_rev, v.delta)).collect();
+        camera[t - 1].pdf_rev = pt_rev;
+        if t > 1 {
+            camera[t - 1].delta = false;
+        }
+        if let Some(pdf) = pt_minus_rev {
+            camera[t - 2].pdf_rev = pdf;
+        }
+        if let Some(pdf) = qs_rev {
+            light[s - 1].pdf_rev = pdf;
+            light[s - 1].delta = false;
+        }
+        if let Some(pdf) = qs_minus_rev {
+            light[s - 2].pdf_rev = pdf;
+        }
+
+        let remap = |f: f64| if f != 0.0 { f } else { 1.0 };
+        let mut sum = 0.0;
+        let mut ri = 1.0;
+        for i in (1..t).rev() {
+            ri *= remap(camera[i].pdf_rev) / remap(camera[i].pdf_fwd);
+            if !camera[i].delta && !camera[i - 1].delta {
+                sum += ri;
+            }
+        }
+        ri = 1.0;
+        for i in (0..s).rev() {
+            ri *= remap(light[i].pdf_rev) / remap(light[i].pdf_fwd);
+            let delta_before = i > 0 && light[i - 1].delta;
+            if !light[i].delta && !delta_before {
+                sum += ri;
+            }
+        }
+
+        for (v, (pdf_rev, delta)) in camera.iter_mut().zip(saved_camera) {
+            v.pdf_rev = pdf_rev;
+            v.delta = delta;
+        }
+        for (v, (pdf_rev, delta)) in light.iter_mut().zip(saved_light) {
+            v.pdf_rev = pdf_rev;
+            v.delta = delta;
+        }
+        1.0 / (1.0 + sum)
+    }
+}
@@ -1,9 +1,33 @@
 use crate::aabb::*;
 use crate::hittable::*;
 use crate::ray::Ray;
+use std::cell::Cell;
 use std::cmp::Ordering;
 use std::f64;
 
+thread_local! {
+    // `None` unless this thread is inside `count_node_visits`, so normal
+    // renders only pay for the check.
+    static NODE_VISITS: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// Runs `f`, counting the nodes its `BVH::hit` calls visit and the objects its
+// `HittableList::hit` calls try, for the traversal-cost debug view.
+pub fn count_node_visits<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    NODE_VISITS.with(|visits| visits.set(Some(0)));
+    let result = f();
+    let visits = NODE_VISITS.with(|visits| visits.take());
+    (result, visits.unwrap_or(0))
+}
+
+pub fn add_node_visits(n: u64) {
+    NODE_VISITS.with(|visits| {
+        if let Some(count) = visits.get() {
+            visits.set(Some(count + n));
+        }
+    });
+}
+
 enum BVHNode {
     Branch { left: Box<BVH>, right: Box<BVH> },
     Leaf(Box<dyn Hittable>),
@@ -94,6 +118,7 @@ impl BVH {
 
 impl Hittable for BVH {
     fn hit(&self, ray: &Ray, t_min: f64, mut t_max: f64) -> Option<HitRecord> {
+        add_node_visits(1);
         if !self.bbox.hit(&ray, t_min, t_max) {
             return None;
         }
@@ -1,6 +1,184 @@
 use crate::ray::Ray;
-use crate::rtweekend::{degrees_to_radians, random_double_range};
+use crate::rtweekend::{degrees_to_radians, random_double};
 use crate::vec3::*;
+use std::f64::consts::PI;
+
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum Projection {
+    Perspective,
+    // Parallel rays along the view direction; `height` is the view height in world units.
+    Orthographic { height: f64 },
+    // Full 360 x 180 degree latitude-longitude panorama around `lookfrom`.
+    Equirectangular,
+    // Equidistant fisheye; `fov` in degrees spans the image width.
+    Fisheye { fov: f64 },
+}
+
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    // Regular polygon with `blades` sides, rotated by `rotation` degrees.
+    Polygon {
+        blades: usize,
+        rotation: f64,
+    },
+    // Grayscale mask over the unit square; brighter texels let more light through.
+    Image {
+        mask: Vec<f64>,
+        nx: usize,
+        ny: usize,
+    },
+}
+
+impl Aperture {
+    #[allow(dead_code)]
+    pub fn from_image(data: &[u8], nx: usize, ny: usize) -> Self {
+        let mask = data
+            .chunks(3)
+            .take(nx * ny)
+            .map(|c| (c[0] as f64 + c[1] as f64 + c[2] as f64) / (3.0 * 255.0))
+            .collect();
+        Aperture::Image { mask, nx, ny }
+    }
+
+    // Point on the lens in [-1, 1]^2, distributed over the aperture shape.
+    fn sample(&self) -> Vec3 {
+        match self {
+            Aperture::Circle => random_in_unit_disk(),
+            Aperture::Polygon { blades, rotation } => {
+                let blades = (*blades).max(3);
+                let step = 2.0 * PI / blades as f64;
+                let i = (random_double() * blades as f64) as usize % blades;
+                let a0 = degrees_to_radians(*rotation) + step * i as f64;
+                let a1 = a0 + step;
+                // Uniform point in the triangle (center, vertex i, vertex i + 1).
+                let mut r1 = random_double();
+                let mut r2 = random_double();
+                if r1 + r2 > 1.0 {
+                    r1 = 1.0 - r1;
+                    r2 = 1.0 - r2;
+                }
+                Vec3::new(
+                    r1 * a0.cos() + r2 * a1.cos(),
+                    r1 * a0.sin() + r2 * a1.sin(),
+                    0.0,
+                )
+            }
+            Aperture::Image { mask, nx, ny } => {
+                for _ in 0..64 {
+                    let x = random_double();
+                    let y = random_double();
+                    let i = ((x * *nx as f64) as usize).min(nx - 1);
+                    let j = (((1.0 - y) * *ny as f64) as usize).min(ny - 1);
+                    if random_double() < mask[i + nx * j] {
+                        return Vec3::new(2.0 * x - 1.0, 2.0 * y - 1.0, 0.0);
+                    }
+                }
+                Vec3::default()
+            }
+        }
+    }
+}
+
+// How exposure is distributed over the shutter interval [time0, time1].
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum Shutter {
+    Uniform,
+    // Opens linearly, fully open at mid-interval, then closes linearly.
+    Triangle,
+    // Exposure linear between `weights` at evenly spaced times; `cdf` has one
+    // entry per segment, ending at 1.
+    Curve { weights: Vec<f64>, cdf: Vec<f64> },
+}
+
+impl Shutter {
+    // Exposure weights at evenly spaced times from open to close.
+    pub fn curve(weights: &[f64]) -> Self {
+        if weights.len() < 2 {
+            return Shutter::Uniform;
+        }
+        let weights: Vec<f64> = weights.iter().map(|w| w.max(0.0)).collect();
+        let mut cdf = Vec::with_capacity(weights.len() - 1);
+        let mut total = 0.0;
+        for pair in weights.windows(2) {
+            total += 0.5 * (pair[0] + pair[1]);
+            cdf.push(total);
+        }
+        if total <= 0.0 {
+            return Shutter::Uniform;
+        }
+        cdf.iter_mut().for_each(|c| *c /= total);
+        Shutter::Curve { weights, cdf }
+    }
+
+    pub fn sample(&self, time0: f64, time1: f64) -> f64 {
+        let r = random_double();
+        let x = match self {
+            Shutter::Uniform => r,
+            Shutter::Triangle => {
+                if r < 0.5 {
+                    0.5 * (2.0 * r).sqrt()
+                } else {
+                    1.0 - 0.5 * (2.0 * (1.0 - r)).sqrt()
+                }
+            }
+            Shutter::Curve { weights, cdf } => {
+                let i = cdf.iter().position(|&c| r < c).unwrap_or(cdf.len() - 1);
+                let c0 = if i == 0 { 0.0 } else { cdf[i - 1] };
+                let u = if cdf[i] > c0 {
+                    (r - c0) / (cdf[i] - c0)
+                } else {
+                    0.5
+                };
+                // Inverts the segment's quadratic CDF, in a form that stays
+                // stable when its ends have equal weight.
+                let (a, b) = (weights[i], weights[i + 1]);
+                let denom = a + ((1.0 - u) * a * a + u * b * b).sqrt();
+                let within = if denom > 0.0 {
+                    (u * (a + b) / denom).min(1.0)
+                } else {
+                    u
+                };
+                (i as f64 + within) / cdf.len() as f64
+            }
+        };
+        time0 + x * (time1 - time0)
+    }
+}
+
+pub trait CameraModel: Send + Sync {
+    fn get_ray(&self, s: f64, t: f64) -> Ray;
+
+    // Ray together with its importance weight. A weight of zero means the
+    // sample was blocked inside the camera, e.g. vignetted by a lens barrel.
+    fn get_weighted_ray(&self, s: f64, t: f64) -> (Ray, f64) {
+        (self.get_ray(s, t), 1.0)
+    }
+
+    // Inverse of `get_ray` for light tracing: where a ray from the lens
+    // toward `p` lands on the image. `None` if `p` is behind the camera or
+    // the camera can't be connected to arbitrary points.
+    fn project(&self, _p: &Point3) -> Option<ImagePoint> {
+        None
+    }
+
+    // Time for a path that doesn't start at the camera, such as a photon,
+    // drawn from the same shutter as the camera's rays.
+    fn sample_time(&self) -> f64;
+}
+
+pub struct ImagePoint {
+    // Image coordinates, as passed to `get_ray`.
+    pub s: f64,
+    pub t: f64,
+    // Point on the lens the ray leaves from.
+    pub lens: Point3,
+    // Solid angle per unit area of (s, t) around the ray.
+    pub solid_angle: f64,
+}
 
 pub struct Camera {
     pub origin: Vec3,
@@ -11,6 +189,13 @@ pub struct Camera {
     pub v: Vec3,
     pub w: Vec3,
     pub lens_radius: f64,
+    aspect_ratio: f64,
+    projection: Projection,
+    aperture: Aperture,
+    // Plane of sharp focus; perpendicular to `w` unless tilted.
+    focus_point: Vec3,
+    focus_normal: Vec3,
+    shutter: Shutter,
     time0: f64,
     time1: f64,
 }
@@ -54,18 +239,118 @@ impl Camera {
             time0,
             time1,
             lens_radius,
+            aspect_ratio,
+            projection: Projection::Perspective,
+            aperture: Aperture::Circle,
+            focus_point: origin - focus_dist * w,
+            focus_normal: w,
+            shutter: Shutter::Uniform,
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * &random_in_unit_disk();
-        let offset = &(&self.u * rd.x()) + &(&self.v * rd.y());
-        Ray::new(
-            &self.origin + &offset,
-            &(&(&(&self.lower_left_corner + &(s * &self.horizontal)) + &(t * &self.vertical))
-                - &self.origin)
-                - &offset,
-            random_double_range(self.time0..self.time1),
-        )
+    // Distance from `lookfrom` to the plane through `focus_point` facing the camera.
+    pub fn focus_distance(lookfrom: &Point3, lookat: &Point3, focus_point: &Point3) -> f64 {
+        let forward = unit_vector(&(lookat - lookfrom));
+        dot(&(focus_point - lookfrom), &forward)
+    }
+
+    #[allow(dead_code)]
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn with_shutter(mut self, shutter: Shutter) -> Self {
+        self.shutter = shutter;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_aperture(mut self, aperture: Aperture) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    // Tilts the plane of focus by `tilt` degrees about the horizontal axis and
+    // `swing` degrees about the vertical axis, and shifts the image window by a
+    // fraction of its width and height without moving the lens.
+    #[allow(dead_code)]
+    pub fn with_tilt_shift(mut self, tilt: f64, swing: f64, shift_x: f64, shift_y: f64) -> Self {
+        let (st, ct) = degrees_to_radians(tilt).sin_cos();
+        let (ss, cs) = degrees_to_radians(swing).sin_cos();
+        let tilted = ct * self.w + st * self.v;
+        self.focus_normal = unit_vector(&(cs * tilted + ss * self.u));
+        self.lower_left_corner += shift_x * self.horizontal + shift_y * self.vertical;
+        self
+    }
+}
+
+impl CameraModel for Camera {
+    fn sample_time(&self) -> f64 {
+        self.shutter.sample(self.time0, self.time1)
+    }
+
+    fn get_ray(&self, s: f64, t: f64) -> Ray {
+        let time = self.sample_time();
+        match &self.projection {
+            Projection::Perspective => {
+                let target = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+                let pinhole_dir = target - self.origin;
+                let denom = dot(&pinhole_dir, &self.focus_normal);
+                let focus = if denom.abs() > 1e-8 {
+                    let k = dot(&(self.focus_point - self.origin), &self.focus_normal) / denom;
+                    self.origin + k * pinhole_dir
+                } else {
+                    target
+                };
+                let rd = self.lens_radius * self.aperture.sample();
+                let offset = rd.x() * self.u + rd.y() * self.v;
+                Ray::new(self.origin + offset, focus - self.origin - offset, time)
+            }
+            Projection::Orthographic { height } => {
+                let x = (s - 0.5) * self.aspect_ratio * height;
+                let y = (t - 0.5) * height;
+                Ray::new(self.origin + x * self.u + y * self.v, -self.w, time)
+            }
+            Projection::Equirectangular => {
+                let phi = (s - 0.5) * 2.0 * PI;
+                let theta = (t - 0.5) * PI;
+                let dir = theta.cos() * phi.sin() * self.u + theta.sin() * self.v
+                    - theta.cos() * phi.cos() * self.w;
+                Ray::new(self.origin, dir, time)
+            }
+            Projection::Fisheye { fov } => {
+                let x = 2.0 * s - 1.0;
+                let y = (2.0 * t - 1.0) / self.aspect_ratio;
+                let theta = (x * x + y * y).sqrt() * degrees_to_radians(*fov) / 2.0;
+                let alpha = y.atan2(x);
+                let dir = theta.sin() * (alpha.cos() * self.u + alpha.sin() * self.v)
+                    - theta.cos() * self.w;
+                Ray::new(self.origin, dir, time)
+            }
+        }
+    }
+
+    // Only pinhole perspective cameras, whose rays all leave from `origin`.
+    fn project(&self, p: &Point3) -> Option<ImagePoint> {
+        if !matches!(self.projection, Projection::Perspective) || self.lens_radius > 0.0 {
+            return None;
+        }
+        let d = p - &self.origin;
+        let depth = -dot(&d, &self.w);
+        if depth <= 0.0 {
+            return None;
+        }
+        // The image window sits at `focus_dist` along -w, shifted or not.
+        let plane = -dot(&(self.lower_left_corner - self.origin), &self.w);
+        let q = self.origin + (plane / depth) * d;
+        let rel = q - self.lower_left_corner;
+        let r = (q - self.origin).length();
+        Some(ImagePoint {
+            s: dot(&rel, &self.horizontal) / self.horizontal.length_squared(),
+            t: dot(&rel, &self.vertical) / self.vertical.length_squared(),
+            lens: self.origin,
+            solid_angle: self.horizontal.length() * self.vertical.length() * plane / (r * r * r),
+        })
     }
 }
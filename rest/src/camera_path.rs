@@ -0,0 +1,162 @@
+use crate::camera::{Camera, CameraModel, ImagePoint, Shutter};
+use crate::ray::Ray;
+use crate::rtweekend::degrees_to_radians;
+use crate::vec3::*;
+
+#[derive(Clone, Copy)]
+pub struct CameraKey {
+    pub time: f64,
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vfov: f64,
+    pub aperture: f64,
+    pub focus_dist: f64,
+}
+
+impl CameraKey {
+    #[allow(dead_code)]
+    pub fn new(
+        time: f64,
+        lookfrom: Point3,
+        lookat: Point3,
+        vfov: f64,
+        aperture: f64,
+        focus_dist: f64,
+    ) -> Self {
+        CameraKey {
+            time,
+            lookfrom,
+            lookat,
+            vfov,
+            aperture,
+            focus_dist,
+        }
+    }
+
+    fn lerp(&self, other: &CameraKey, time: f64) -> CameraKey {
+        let t = (time - self.time) / (other.time - self.time);
+        let mix = |a: f64, b: f64| a + t * (b - a);
+        CameraKey {
+            time,
+            lookfrom: lerp(&self.lookfrom, &other.lookfrom, t),
+            lookat: lerp(&self.lookat, &other.lookat, t),
+            vfov: mix(self.vfov, other.vfov),
+            aperture: mix(self.aperture, other.aperture),
+            focus_dist: mix(self.focus_dist, other.focus_dist),
+        }
+    }
+}
+
+// Camera parameters keyed over time, interpolated linearly and held constant
+// outside the first and last key.
+pub struct CameraPath {
+    keys: Vec<CameraKey>,
+    vup: Vec3,
+}
+
+impl CameraPath {
+    #[allow(dead_code)]
+    pub fn new(mut keys: Vec<CameraKey>, vup: Vec3) -> Self {
+        if keys.is_empty() {
+            panic!["camera path needs at least one key"]
+        }
+        keys.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        CameraPath { keys, vup }
+    }
+
+    pub fn at(&self, time: f64) -> CameraKey {
+        let first = &self.keys[0];
+        if time <= first.time {
+            return *first;
+        }
+        for pair in self.keys.windows(2) {
+            if time <= pair[1].time {
+                return pair[0].lerp(&pair[1], time);
+            }
+        }
+        self.keys[self.keys.len() - 1]
+    }
+
+    // Camera following the path through the shutter interval [time0, time1].
+    pub fn camera(&self, aspect_ratio: f64, time0: f64, time1: f64) -> PathCamera<'_> {
+        let still = if time0 == time1 {
+            let key = self.at(time0);
+            Some(Camera::new(
+                key.lookfrom,
+                key.lookat,
+                self.vup,
+                key.vfov,
+                aspect_ratio,
+                key.aperture,
+                key.focus_dist,
+                time0,
+                time1,
+            ))
+        } else {
+            None
+        };
+        PathCamera {
+            path: self,
+            aspect_ratio,
+            time0,
+            time1,
+            shutter: Shutter::Uniform,
+            still,
+        }
+    }
+}
+
+// Each ray leaves from the pose at its own sample time, so the camera's own
+// motion blurs like everything else's. Poses differ from ray to ray, so it
+// can't be projected onto and BDPT leaves out light tracing to it, unless
+// the shutter is closed to a single instant.
+pub struct PathCamera<'a> {
+    path: &'a CameraPath,
+    aspect_ratio: f64,
+    time0: f64,
+    time1: f64,
+    shutter: Shutter,
+    // The one pose when `time0 == time1`, built once up front.
+    still: Option<Camera>,
+}
+
+impl<'a> PathCamera<'a> {
+    pub fn with_shutter(mut self, shutter: Shutter) -> Self {
+        self.shutter = shutter;
+        self
+    }
+
+    // Thin-lens ray through (s, t) from the pose at `time`, as `Camera`
+    // would trace it, without setting up a whole `Camera`.
+    fn ray_at(&self, s: f64, t: f64, time: f64) -> Ray {
+        let key = self.path.at(time);
+        let half_height = (degrees_to_radians(key.vfov) / 2.0).tan();
+        let half_width = self.aspect_ratio * half_height;
+        let w = unit_vector(&(key.lookfrom - key.lookat));
+        let u = unit_vector(&cross(&self.path.vup, &w));
+        let v = cross(&w, &u);
+        let focus = key.lookfrom
+            + key.focus_dist
+                * ((2.0 * s - 1.0) * half_width * u + (2.0 * t - 1.0) * half_height * v - w);
+        let rd = 0.5 * key.aperture * random_in_unit_disk();
+        let offset = rd.x() * u + rd.y() * v;
+        Ray::new(key.lookfrom + offset, focus - key.lookfrom - offset, time)
+    }
+}
+
+impl<'a> CameraModel for PathCamera<'a> {
+    fn get_ray(&self, s: f64, t: f64) -> Ray {
+        match &self.still {
+            Some(camera) => camera.get_ray(s, t),
+            None => self.ray_at(s, t, self.sample_time()),
+        }
+    }
+
+    fn project(&self, p: &Point3) -> Option<ImagePoint> {
+        self.still.as_ref()?.project(p)
+    }
+
+    fn sample_time(&self) -> f64 {
+        self.shutter.sample(self.time0, self.time1)
+    }
+}
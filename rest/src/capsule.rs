@@ -0,0 +1,182 @@
+use crate::aabb::AABB;
+use crate::hittable::*;
+use crate::material::Material;
+use crate::onb::ONB;
+use crate::pdf::{surface_pdf_value, SURFACE_PDF_TOLERANCE};
+use crate::ray::Ray;
+use crate::sampler;
+use crate::sphere::get_sphere_uv;
+use crate::vec3::*;
+use rand::Rng;
+use std::f64;
+
+// All points within `radius` of the segment from `p0` to `p1`: a cylinder
+// closed by two hemispheres.
+#[derive(Clone)]
+pub struct Capsule<M: Material> {
+    p0: Point3,
+    p1: Point3,
+    radius: f64,
+    length: f64,
+    // Frame with `axis` running from `p0` to `p1`.
+    axis: Vec3,
+    axis_u: Vec3,
+    axis_v: Vec3,
+    material: M,
+}
+
+impl<M: Material> Capsule<M> {
+    #[allow(dead_code)]
+    pub fn new(p0: Point3, p1: Point3, radius: f64, material: M) -> Self {
+        let length = (p1 - p0).length();
+        let frame = if length > 1e-12 {
+            ONB::build_from_w(&(p1 - p0))
+        } else {
+            ONB::build_from_w(&Vec3::new(0.0, 1.0, 0.0))
+        };
+        Capsule {
+            p0,
+            p1,
+            radius,
+            length,
+            axis: frame.w(),
+            axis_u: frame.u(),
+            axis_v: frame.v(),
+            material,
+        }
+    }
+
+    fn area(&self) -> (f64, f64) {
+        let side = 2.0 * f64::consts::PI * self.radius * self.length;
+        let ends = 4.0 * f64::consts::PI * self.radius * self.radius;
+        (side, ends)
+    }
+}
+
+// Both crossings of the sphere of `radius` around `center`.
+fn sphere_roots(center: &Point3, radius: f64, ray: &Ray) -> Vec<f64> {
+    let oc = ray.origin() - center;
+    let a = ray.direction().length_squared();
+    let half_b = dot(&oc, ray.direction());
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    let sqrtd = discriminant.sqrt();
+    vec![(-half_b - sqrtd) / a, (-half_b + sqrtd) / a]
+}
+
+impl<M: Material> Hittable for Capsule<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let oa = ray.origin() - &self.p0;
+        let d = ray.direction();
+        let height = |t: f64| dot(&(oa + t * d), &self.axis);
+        let mut candidates = Vec::with_capacity(6);
+
+        // Infinite cylinder around the axis, kept between the end points.
+        let d_perp = d - &(dot(d, &self.axis) * self.axis);
+        let o_perp = oa - dot(&oa, &self.axis) * self.axis;
+        let a = d_perp.length_squared();
+        if a > 1e-12 {
+            let half_b = dot(&d_perp, &o_perp);
+            let c = o_perp.length_squared() - self.radius * self.radius;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant >= 0.0 {
+                let sqrtd = discriminant.sqrt();
+                candidates.push((-half_b - sqrtd) / a);
+                candidates.push((-half_b + sqrtd) / a);
+            }
+        }
+        candidates.retain(|&t| {
+            let y = height(t);
+            y >= 0.0 && y <= self.length
+        });
+        // End caps, each keeping only the half beyond its end point.
+        for t in sphere_roots(&self.p0, self.radius, ray) {
+            if height(t) < 0.0 {
+                candidates.push(t);
+            }
+        }
+        for t in sphere_roots(&self.p1, self.radius, ray) {
+            if height(t) > self.length {
+                candidates.push(t);
+            }
+        }
+        let t = candidates
+            .into_iter()
+            .filter(|&t| t >= t_min && t <= t_max)
+            .fold(None, |best: Option<f64>, t| {
+                Some(best.map_or(t, |b| b.min(t)))
+            })?;
+
+        let p = ray.at(t);
+        let local = p - self.p0;
+        let y = dot(&local, &self.axis);
+        let closest = self.p0 + y.max(0.0).min(self.length) * self.axis;
+        let outward_normal = (p - closest) / self.radius;
+
+        // u goes around the axis as in `get_sphere_uv`, v runs from the tip
+        // of the first cap to the tip of the second.
+        let x = dot(&local, &self.axis_u);
+        let z = dot(&local, &self.axis_v);
+        let (u, _) = get_sphere_uv(&Vec3::new(x, 0.0, z));
+        let span = self.length + 2.0 * self.radius;
+        let v = (y + self.radius) / span;
+        let dpdu = 2.0 * f64::consts::PI * (z * self.axis_u - x * self.axis_v);
+        let rho = (x * x + z * z).sqrt();
+        let dpdv = if rho > 1e-12 {
+            let mut meridian = unit_vector(&cross(&dpdu, &outward_normal));
+            if dot(&meridian, &self.axis) < 0.0 {
+                meridian = -meridian;
+            }
+            (span * self.radius / rho) * meridian
+        } else {
+            Vec3::default()
+        };
+        Some(
+            HitRecord::new(p, &self.material, t, u, v, ray, outward_normal)
+                .with_tangents(dpdu, dpdv),
+        )
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let mut min = self.p0;
+        let mut max = self.p0;
+        for a in 0..3 {
+            min[a] = min[a].min(self.p1[a]) - self.radius;
+            max[a] = max[a].max(self.p1[a]) + self.radius;
+        }
+        Some(AABB::new(min, max))
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        let (side, ends) = self.area();
+        surface_pdf_value(self, o, v, side + ends)
+    }
+
+    fn sampling_tolerance(&self) -> f64 {
+        SURFACE_PDF_TOLERANCE
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        let mut rng = sampler::rng();
+        let (side, ends) = self.area();
+        let p = if rng.gen::<f64>() * (side + ends) < side {
+            let phi = 2.0 * f64::consts::PI * rng.gen::<f64>();
+            let y = self.length * rng.gen::<f64>();
+            let around = phi.cos() * self.axis_u + phi.sin() * self.axis_v;
+            self.p0 + y * self.axis + self.radius * around
+        } else {
+            // The two hemispheres together make up one whole sphere.
+            let dir = random_unit_vector();
+            let center = if dot(&dir, &self.axis) < 0.0 {
+                self.p0
+            } else {
+                self.p1
+            };
+            center + self.radius * dir
+        };
+        p - o
+    }
+}
@@ -47,3 +47,8 @@ pub fn get_color(pixel_color: Color, samples_per_pixel: i32) -> (i32, i32, i32)
         (256.0 * b.clamp(0.0, 0.999)) as i32,
     )
 }
+
+// Relative luminance of a linear RGB color (Rec. 709 weights).
+pub fn luminance(c: &Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
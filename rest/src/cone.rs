@@ -0,0 +1,169 @@
+use crate::aabb::AABB;
+use crate::hittable::*;
+use crate::material::Material;
+use crate::pdf::{surface_pdf_value, SURFACE_PDF_TOLERANCE};
+use crate::ray::Ray;
+use crate::sampler;
+use crate::sphere::get_sphere_uv;
+use crate::vec3::*;
+use rand::Rng;
+use std::f64;
+
+// Cone around the y axis with its base disk at `base` and its apex at
+// `base + height`, optionally closed at the base. Use `Rotate` and
+// `Translate` to orient it.
+#[derive(Clone)]
+pub struct Cone<M: Material> {
+    base: Point3,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: M,
+}
+
+impl<M: Material> Cone<M> {
+    #[allow(dead_code)]
+    pub fn new(base: Point3, radius: f64, height: f64, capped: bool, material: M) -> Self {
+        Cone {
+            base,
+            radius,
+            height,
+            capped,
+            material,
+        }
+    }
+
+    fn area(&self) -> (f64, f64) {
+        let slant = (self.radius * self.radius + self.height * self.height).sqrt();
+        let side = f64::consts::PI * self.radius * slant;
+        let cap = if self.capped {
+            f64::consts::PI * self.radius * self.radius
+        } else {
+            0.0
+        };
+        (side, cap)
+    }
+}
+
+impl<M: Material> Hittable for Cone<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let o = ray.origin() - &self.base;
+        let d = ray.direction();
+        let mut closest: Option<(f64, bool)> = None;
+        let mut consider = |t: f64, side: bool| {
+            if t >= t_min && t <= t_max && closest.is_none_or(|(best, _)| t < best) {
+                closest = Some((t, side));
+            }
+        };
+
+        // x^2 + z^2 = (k(h - y))^2 with k = radius / height.
+        let k2 = (self.radius / self.height).powi(2);
+        let h = self.height - o.y();
+        let a = d.x() * d.x() + d.z() * d.z() - k2 * d.y() * d.y();
+        let half_b = o.x() * d.x() + o.z() * d.z() + k2 * h * d.y();
+        let c = o.x() * o.x() + o.z() * o.z() - k2 * h * h;
+        let roots = if a.abs() < 1e-12 {
+            // Parallel to the slant: only one crossing.
+            if half_b.abs() < 1e-12 {
+                vec![]
+            } else {
+                vec![-c / (2.0 * half_b)]
+            }
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                vec![]
+            } else {
+                let sqrtd = discriminant.sqrt();
+                vec![(-half_b - sqrtd) / a, (-half_b + sqrtd) / a]
+            }
+        };
+        for t in roots {
+            let y = o.y() + t * d.y();
+            if y >= 0.0 && y <= self.height {
+                consider(t, true);
+            }
+        }
+        if self.capped && d.y().abs() > 1e-12 {
+            let t = -o.y() / d.y();
+            let x = o.x() + t * d.x();
+            let z = o.z() + t * d.z();
+            if x * x + z * z <= self.radius * self.radius {
+                consider(t, false);
+            }
+        }
+
+        let (t, side) = closest?;
+        let p = ray.at(t);
+        let local = p - self.base;
+        let (outward_normal, u, v, dpdu, dpdv) = if side {
+            let radial = Vec3::new(local.x(), 0.0, local.z());
+            let rho = radial.length();
+            let (u, _) = get_sphere_uv(&radial);
+            let normal = unit_vector(&Vec3::new(
+                local.x(),
+                k2 * (self.height - local.y()),
+                local.z(),
+            ));
+            // Both derivatives vanish at the apex.
+            let dpdv = if rho > 0.0 {
+                Vec3::new(
+                    -self.radius * local.x() / rho,
+                    self.height,
+                    -self.radius * local.z() / rho,
+                )
+            } else {
+                Vec3::default()
+            };
+            (
+                normal,
+                u,
+                local.y() / self.height,
+                2.0 * f64::consts::PI * Vec3::new(local.z(), 0.0, -local.x()),
+                dpdv,
+            )
+        } else {
+            (
+                Vec3::new(0.0, -1.0, 0.0),
+                0.5 * (local.x() / self.radius + 1.0),
+                0.5 * (local.z() / self.radius + 1.0),
+                Vec3::new(2.0 * self.radius, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 2.0 * self.radius),
+            )
+        };
+        Some(
+            HitRecord::new(p, &self.material, t, u, v, ray, outward_normal)
+                .with_tangents(dpdu, dpdv),
+        )
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(AABB::new(
+            self.base - Vec3::new(self.radius, 0.0, self.radius),
+            self.base + Vec3::new(self.radius, self.height, self.radius),
+        ))
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        let (side, cap) = self.area();
+        surface_pdf_value(self, o, v, side + cap)
+    }
+
+    fn sampling_tolerance(&self) -> f64 {
+        SURFACE_PDF_TOLERANCE
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        let mut rng = sampler::rng();
+        let (side, cap) = self.area();
+        let phi = 2.0 * f64::consts::PI * rng.gen::<f64>();
+        let (rho, y) = if rng.gen::<f64>() * (side + cap) < side {
+            // Area grows linearly with the distance from the apex.
+            let f = rng.gen::<f64>().sqrt();
+            (self.radius * f, self.height * (1.0 - f))
+        } else {
+            (self.radius * rng.gen::<f64>().sqrt(), 0.0)
+        };
+        self.base + Vec3::new(rho * phi.cos(), y, rho * phi.sin()) - o
+    }
+}
@@ -0,0 +1,98 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::ray::Ray;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // Inside `a` but not `b`.
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// Boolean combination of two closed hittables. The ray is walked through the
+// surface crossings of both operands in order, and the first crossing that
+// changes whether we are inside the combined solid is reported.
+pub struct Csg<A: Hittable, B: Hittable> {
+    op: CsgOp,
+    a: A,
+    b: B,
+}
+
+impl<A: Hittable, B: Hittable> Csg<A, B> {
+    #[allow(dead_code)]
+    pub fn new(op: CsgOp, a: A, b: B) -> Self {
+        Csg { op, a, b }
+    }
+}
+
+// Entering or leaving a closed shape, and the next crossing after it.
+fn advance<'a>(
+    hittable: &'a dyn Hittable,
+    ray: &Ray,
+    next: &mut Option<HitRecord<'a>>,
+    inside: &mut bool,
+) -> HitRecord<'a> {
+    let rec = next.take().unwrap();
+    *inside = rec.front_face;
+    *next = hittable.hit(ray, rec.t + 0.0001, f64::MAX);
+    rec
+}
+
+impl<A: Hittable, B: Hittable> Hittable for Csg<A, B> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut next_a = self.a.hit(ray, t_min, f64::MAX);
+        let mut next_b = self.b.hit(ray, t_min, f64::MAX);
+        // If the first crossing of a closed shape is an exit, we start inside it.
+        let mut in_a = next_a.as_ref().is_some_and(|rec| !rec.front_face);
+        let mut in_b = next_b.as_ref().is_some_and(|rec| !rec.front_face);
+        let inside = self.op.inside(in_a, in_b);
+
+        loop {
+            let mut rec = match (&next_a, &next_b) {
+                (Some(a), Some(b)) if a.t <= b.t => advance(&self.a, ray, &mut next_a, &mut in_a),
+                (Some(_), None) => advance(&self.a, ray, &mut next_a, &mut in_a),
+                (_, Some(_)) => advance(&self.b, ray, &mut next_b, &mut in_b),
+                (None, None) => return None,
+            };
+            if rec.t > t_max {
+                return None;
+            }
+            if self.op.inside(in_a, in_b) != inside {
+                // The normal already faces the ray; only which side of the
+                // combined surface we hit can differ from the operand's.
+                rec.front_face = !inside;
+                return Some(rec);
+            }
+        }
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        let box_a = self.a.bounding_box(t0, t1)?;
+        let box_b = self.b.bounding_box(t0, t1)?;
+        match self.op {
+            CsgOp::Union => Some(surrounding_box(&box_a, &box_b)),
+            CsgOp::Intersection => {
+                let mut min = box_a.min;
+                let mut max = box_a.max;
+                for a in 0..3 {
+                    min[a] = min[a].max(box_b.min[a]);
+                    max[a] = max[a].min(box_b.max[a]).max(min[a]);
+                }
+                Some(AABB::new(min, max))
+            }
+            CsgOp::Difference => Some(box_a),
+        }
+    }
+}
@@ -88,4 +88,12 @@ impl Hittable for Cube {
             max: self.p_max,
         })
     }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        self.sides.pdf_value(o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        self.sides.random(o)
+    }
 }
@@ -0,0 +1,324 @@
+use crate::aabb::AABB;
+use crate::hittable::*;
+use crate::material::Material;
+use crate::onb::ONB;
+use crate::ray::Ray;
+use crate::vec3::*;
+use std::f64;
+use std::sync::Arc;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum CurveType {
+    // Flat strip that always faces the incoming ray.
+    Flat,
+    // Like `Flat`, but shaded as a round tube; what the `Hair` material expects.
+    Cylinder,
+    // Strip with a fixed orientation, given by surface normals at either end.
+    Ribbon { n0: Vec3, n1: Vec3 },
+}
+
+fn lerp_f64(a: f64, b: f64, t: f64) -> f64 {
+    (1.0 - t) * a + t * b
+}
+
+// Splits a cubic Bezier at its midpoint into two halves sharing cp[3].
+fn subdivide_bezier(cp: &[Vec3; 4]) -> [Vec3; 7] {
+    let mid = |a: &Vec3, b: &Vec3| 0.5 * (a + b);
+    let p01 = mid(&cp[0], &cp[1]);
+    let p12 = mid(&cp[1], &cp[2]);
+    let p23 = mid(&cp[2], &cp[3]);
+    let p012 = mid(&p01, &p12);
+    let p123 = mid(&p12, &p23);
+    let p0123 = mid(&p012, &p123);
+    [cp[0], p01, p012, p0123, p123, p23, cp[3]]
+}
+
+// Point on the curve at `u` and the derivative there.
+fn eval_bezier(cp: &[Vec3; 4], u: f64) -> (Vec3, Vec3) {
+    let cp1 = [
+        lerp(&cp[0], &cp[1], u),
+        lerp(&cp[1], &cp[2], u),
+        lerp(&cp[2], &cp[3], u),
+    ];
+    let cp2 = [lerp(&cp1[0], &cp1[1], u), lerp(&cp1[1], &cp1[2], u)];
+    let derivative = if (cp2[1] - cp2[0]).length_squared() > 0.0 {
+        3.0 * (cp2[1] - cp2[0])
+    } else {
+        // Coincident control points at an end; fall back to the chord.
+        cp[3] - cp[0]
+    };
+    (lerp(&cp2[0], &cp2[1], u), derivative)
+}
+
+// Control points of the part of the curve between `u0` and `u1`.
+fn blossom_segment(cp: &[Vec3; 4], u0: f64, u1: f64) -> [Vec3; 4] {
+    let blossom = |a: f64, b: f64, c: f64| {
+        let p = [
+            lerp(&cp[0], &cp[1], a),
+            lerp(&cp[1], &cp[2], a),
+            lerp(&cp[2], &cp[3], a),
+        ];
+        let q = [lerp(&p[0], &p[1], b), lerp(&p[1], &p[2], b)];
+        lerp(&q[0], &q[1], c)
+    };
+    [
+        blossom(u0, u0, u0),
+        blossom(u0, u0, u1),
+        blossom(u0, u1, u1),
+        blossom(u1, u1, u1),
+    ]
+}
+
+struct CurveCommon {
+    cp: [Point3; 4],
+    width: [f64; 2],
+    curve_type: CurveType,
+}
+
+impl CurveCommon {
+    fn width(&self, u: f64) -> f64 {
+        lerp_f64(self.width[0], self.width[1], u)
+    }
+}
+
+// Ray-space hit on the curve: distance along the unit ray direction, curve
+// parameter, position across the width, and the width there.
+struct CurveHit {
+    z: f64,
+    u: f64,
+    v: f64,
+    width: f64,
+}
+
+// One piece, covering [u_min, u_max], of a cubic Bezier curve of varying
+// width. Build whole curves with `curve_segments` so the `BVH` can bound
+// each piece tightly.
+pub struct Curve<M: Material> {
+    common: Arc<CurveCommon>,
+    u_min: f64,
+    u_max: f64,
+    // Control points of just this segment.
+    cp: [Point3; 4],
+    material: Arc<M>,
+}
+
+// Splits the curve through `cp` into `segments` pieces. The width varies
+// linearly from `w0` to `w1` along the curve.
+#[allow(dead_code)]
+pub fn curve_segments<M: Material + 'static>(
+    cp: [Point3; 4],
+    w0: f64,
+    w1: f64,
+    curve_type: CurveType,
+    segments: usize,
+    material: Arc<M>,
+) -> Vec<Box<dyn Hittable>> {
+    let curve_type = match curve_type {
+        CurveType::Ribbon { n0, n1 } => CurveType::Ribbon {
+            n0: unit_vector(&n0),
+            n1: unit_vector(&n1),
+        },
+        t => t,
+    };
+    let common = Arc::new(CurveCommon {
+        cp,
+        width: [w0, w1],
+        curve_type,
+    });
+    (0..segments)
+        .map(|i| {
+            let u_min = i as f64 / segments as f64;
+            let u_max = (i + 1) as f64 / segments as f64;
+            Box::new(Curve {
+                common: common.clone(),
+                u_min,
+                u_max,
+                cp: blossom_segment(&cp, u_min, u_max),
+                material: material.clone(),
+            }) as Box<dyn Hittable>
+        })
+        .collect()
+}
+
+impl<M: Material> Curve<M> {
+    // Pbrt-style recursive intersection in ray space, where the ray runs
+    // down the z axis from the origin. Halves are culled by their bounding
+    // boxes until the recursion depth is used up; the leaves are then
+    // treated as straight segments. `dir` is the unit ray direction in the
+    // space of the curve, which ribbons are seen edge-on against.
+    fn recursive_intersect(
+        &self,
+        cp: &[Vec3; 4],
+        dir: &Vec3,
+        (u0, u1): (f64, f64),
+        depth: u32,
+        (z_min, z_max): (f64, f64),
+    ) -> Option<CurveHit> {
+        if depth > 0 {
+            let split = subdivide_bezier(cp);
+            let u = [u0, 0.5 * (u0 + u1), u1];
+            let mut z_max = z_max;
+            let mut closest = None;
+            for seg in 0..2 {
+                let half = [
+                    split[3 * seg],
+                    split[3 * seg + 1],
+                    split[3 * seg + 2],
+                    split[3 * seg + 3],
+                ];
+                let half_width = 0.5 * self.common.width(u[seg]).max(self.common.width(u[seg + 1]));
+                let (min, max) = bounds(&half);
+                if max.x() + half_width < 0.0
+                    || min.x() - half_width > 0.0
+                    || max.y() + half_width < 0.0
+                    || min.y() - half_width > 0.0
+                    || max.z() + half_width < z_min
+                    || min.z() - half_width > z_max
+                {
+                    continue;
+                }
+                if let Some(hit) = self.recursive_intersect(
+                    &half,
+                    dir,
+                    (u[seg], u[seg + 1]),
+                    depth - 1,
+                    (z_min, z_max),
+                ) {
+                    z_max = hit.z;
+                    closest = Some(hit);
+                }
+            }
+            return closest;
+        }
+
+        // The ray must pass between the planes through each end point that
+        // are perpendicular to the curve there.
+        let edge = (cp[1].y() - cp[0].y()) * -cp[0].y() + cp[0].x() * (cp[0].x() - cp[1].x());
+        if edge < 0.0 {
+            return None;
+        }
+        let edge = (cp[2].y() - cp[3].y()) * -cp[3].y() + cp[3].x() * (cp[3].x() - cp[2].x());
+        if edge < 0.0 {
+            return None;
+        }
+
+        // Closest point to the ray on the straight segment.
+        let segment = Vec3::new(cp[3].x() - cp[0].x(), cp[3].y() - cp[0].y(), 0.0);
+        let denom = segment.length_squared();
+        if denom == 0.0 {
+            return None;
+        }
+        let w = (-cp[0].x() * segment.x() - cp[0].y() * segment.y()) / denom;
+        let u = lerp_f64(u0, u1, w).clamp(u0, u1);
+        let (pc, tangent) = eval_bezier(cp, w.clamp(0.0, 1.0));
+        let mut width = self.common.width(u);
+        if let CurveType::Ribbon { n0, n1 } = self.common.curve_type {
+            // A ribbon seen edge-on gets thinner.
+            width *= dot(&unit_vector(&lerp(&n0, &n1, u)), dir).abs();
+        }
+        let distance_squared = pc.x() * pc.x() + pc.y() * pc.y();
+        if distance_squared > 0.25 * width * width || pc.z() < z_min || pc.z() > z_max {
+            return None;
+        }
+
+        // v runs across the curve, from 0 on one edge to 1 on the other.
+        let tangent_length = (tangent.x() * tangent.x() + tangent.y() * tangent.y()).sqrt();
+        let across = (pc.x() * tangent.y() - pc.y() * tangent.x()) / tangent_length;
+        Some(CurveHit {
+            z: pc.z(),
+            u,
+            v: 0.5 - across / width,
+            width,
+        })
+    }
+}
+
+fn bounds(cp: &[Vec3; 4]) -> (Vec3, Vec3) {
+    let mut min = cp[0];
+    let mut max = cp[0];
+    for p in cp.iter().skip(1) {
+        for a in 0..3 {
+            min[a] = min[a].min(p[a]);
+            max[a] = max[a].max(p[a]);
+        }
+    }
+    (min, max)
+}
+
+impl<M: Material> Hittable for Curve<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let length = ray.direction().length();
+        let dir = ray.direction() / length;
+        let frame = ONB::build_from_w(&dir);
+        let to_ray = |p: &Vec3| {
+            let q = p - ray.origin();
+            Vec3::new(dot(&q, &frame.u()), dot(&q, &frame.v()), dot(&q, &dir))
+        };
+        let cp = [
+            to_ray(&self.cp[0]),
+            to_ray(&self.cp[1]),
+            to_ray(&self.cp[2]),
+            to_ray(&self.cp[3]),
+        ];
+
+        // Enough subdivisions to bring the segments within a small fraction
+        // of the width of a straight line.
+        let mut l0: f64 = 0.0;
+        for i in 0..2 {
+            let second = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+            l0 = l0
+                .max(second.x().abs())
+                .max(second.y().abs())
+                .max(second.z().abs());
+        }
+        let eps = 0.05 * self.common.width[0].max(self.common.width[1]);
+        let depth = if l0 > 0.0 {
+            let r0 = (f64::consts::SQRT_2 * 6.0 * l0 / (8.0 * eps)).log2() / 2.0;
+            r0.clamp(0.0, 10.0) as u32
+        } else {
+            0
+        };
+
+        let hit = self.recursive_intersect(
+            &cp,
+            &dir,
+            (self.u_min, self.u_max),
+            depth,
+            (t_min * length, t_max.min(f64::MAX / length) * length),
+        )?;
+
+        let t = hit.z / length;
+        let p = ray.at(t);
+        let (_, dpdu) = eval_bezier(&self.common.cp, hit.u);
+        let (normal, dpdv) = match self.common.curve_type {
+            CurveType::Ribbon { n0, n1 } => {
+                let n = unit_vector(&lerp(&n0, &n1, hit.u));
+                (n, hit.width * unit_vector(&cross(&n, &dpdu)))
+            }
+            _ => {
+                let dpdv = hit.width * unit_vector(&cross(&dpdu, &dir));
+                (unit_vector(&cross(&dpdu, &dpdv)), dpdv)
+            }
+        };
+        let mut rec = HitRecord::new(p, &*self.material, t, hit.u, hit.v, ray, normal)
+            .with_tangents(dpdu, dpdv);
+        if let CurveType::Cylinder = self.common.curve_type {
+            // Bend the normal around the tube: sideways at either edge.
+            let theta = (hit.v - 0.5) * f64::consts::PI;
+            rec.shading_normal = theta.cos() * rec.normal + theta.sin() * unit_vector(&dpdv);
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let (min, max) = bounds(&self.cp);
+        let half_width = 0.5
+            * self
+                .common
+                .width(self.u_min)
+                .max(self.common.width(self.u_max));
+        let pad = Vec3::new(half_width, half_width, half_width);
+        Some(AABB::new(min - pad, max + pad))
+    }
+}
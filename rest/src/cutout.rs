@@ -0,0 +1,76 @@
+use crate::aabb::AABB;
+use crate::color::luminance;
+use crate::hittable::*;
+use crate::ray::Ray;
+use crate::rtweekend::random_double;
+use crate::texture::Texture;
+use crate::vec3::*;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum AlphaMode {
+    // Keep hits whose alpha is at least the threshold.
+    Threshold(f64),
+    // Keep hits with probability equal to alpha, for soft edges.
+    Stochastic,
+}
+
+// Ignores hits where the opacity texture (its luminance) says the surface is
+// transparent, and continues the search behind them. Leaves and fences can be
+// cut out of flat geometry this way.
+pub struct Cutout<H: Hittable, T: Texture> {
+    hittable: H,
+    alpha: T,
+    mode: AlphaMode,
+}
+
+impl<H: Hittable, T: Texture> Cutout<H, T> {
+    #[allow(dead_code)]
+    pub fn new(hittable: H, alpha: T, mode: AlphaMode) -> Self {
+        Cutout {
+            hittable,
+            alpha,
+            mode,
+        }
+    }
+
+    fn is_opaque(&self, rec: &HitRecord) -> bool {
+        let alpha = luminance(&self.alpha.value(rec.u, rec.v, &rec.p));
+        match self.mode {
+            AlphaMode::Threshold(threshold) => alpha >= threshold,
+            AlphaMode::Stochastic => random_double() < alpha,
+        }
+    }
+}
+
+impl<H: Hittable, T: Texture> Hittable for Cutout<H, T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut t_start = t_min;
+        while let Some(rec) = self.hittable.hit(ray, t_start, t_max) {
+            if self.is_opaque(&rec) {
+                return Some(rec);
+            }
+            t_start = rec.t + 0.0001;
+        }
+        None
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.hittable.bounding_box(t0, t1)
+    }
+
+    // Light sampling goes straight to the wrapped shape: `random` may pick a
+    // direction through a hole, but `pdf_value` reports the same density it was
+    // sampled with, so the estimate stays unbiased.
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        self.hittable.pdf_value(o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        self.hittable.random(o)
+    }
+
+    fn sampling_tolerance(&self) -> f64 {
+        self.hittable.sampling_tolerance()
+    }
+}
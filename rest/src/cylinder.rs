@@ -0,0 +1,154 @@
+use crate::aabb::AABB;
+use crate::hittable::*;
+use crate::material::Material;
+use crate::pdf::{surface_pdf_value, SURFACE_PDF_TOLERANCE};
+use crate::ray::Ray;
+use crate::sampler;
+use crate::sphere::get_sphere_uv;
+use crate::vec3::*;
+use rand::Rng;
+use std::f64;
+
+#[derive(Clone, Copy)]
+enum Part {
+    Side,
+    Bottom,
+    Top,
+}
+
+// Cylinder around the y axis, from `base` up to `base + height`, optionally
+// closed with flat caps. Use `Rotate` and `Translate` to orient it.
+#[derive(Clone)]
+pub struct Cylinder<M: Material> {
+    base: Point3,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: M,
+}
+
+impl<M: Material> Cylinder<M> {
+    #[allow(dead_code)]
+    pub fn new(base: Point3, radius: f64, height: f64, capped: bool, material: M) -> Self {
+        Cylinder {
+            base,
+            radius,
+            height,
+            capped,
+            material,
+        }
+    }
+
+    fn area(&self) -> (f64, f64) {
+        let side = 2.0 * f64::consts::PI * self.radius * self.height;
+        let cap = if self.capped {
+            f64::consts::PI * self.radius * self.radius
+        } else {
+            0.0
+        };
+        (side, cap)
+    }
+}
+
+impl<M: Material> Hittable for Cylinder<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let o = ray.origin() - &self.base;
+        let d = ray.direction();
+        let mut closest: Option<(f64, Part)> = None;
+        let mut consider = |t: f64, part: Part| {
+            if t >= t_min && t <= t_max && closest.is_none_or(|(best, _)| t < best) {
+                closest = Some((t, part));
+            }
+        };
+
+        let a = d.x() * d.x() + d.z() * d.z();
+        if a > 1e-12 {
+            let half_b = o.x() * d.x() + o.z() * d.z();
+            let c = o.x() * o.x() + o.z() * o.z() - self.radius * self.radius;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant >= 0.0 {
+                let sqrtd = discriminant.sqrt();
+                for &t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a].iter() {
+                    let y = o.y() + t * d.y();
+                    if y >= 0.0 && y <= self.height {
+                        consider(t, Part::Side);
+                    }
+                }
+            }
+        }
+        if self.capped && d.y().abs() > 1e-12 {
+            for &(y, part) in [(0.0, Part::Bottom), (self.height, Part::Top)].iter() {
+                let t = (y - o.y()) / d.y();
+                let x = o.x() + t * d.x();
+                let z = o.z() + t * d.z();
+                if x * x + z * z <= self.radius * self.radius {
+                    consider(t, part);
+                }
+            }
+        }
+
+        let (t, part) = closest?;
+        let p = ray.at(t);
+        let local = p - self.base;
+        let (outward_normal, u, v, dpdu, dpdv) = match part {
+            Part::Side => {
+                let radial = Vec3::new(local.x(), 0.0, local.z());
+                let (u, _) = get_sphere_uv(&radial);
+                (
+                    radial / self.radius,
+                    u,
+                    local.y() / self.height,
+                    2.0 * f64::consts::PI * Vec3::new(local.z(), 0.0, -local.x()),
+                    Vec3::new(0.0, self.height, 0.0),
+                )
+            }
+            Part::Bottom | Part::Top => {
+                let normal = match part {
+                    Part::Bottom => Vec3::new(0.0, -1.0, 0.0),
+                    _ => Vec3::new(0.0, 1.0, 0.0),
+                };
+                (
+                    normal,
+                    0.5 * (local.x() / self.radius + 1.0),
+                    0.5 * (local.z() / self.radius + 1.0),
+                    Vec3::new(2.0 * self.radius, 0.0, 0.0),
+                    Vec3::new(0.0, 0.0, 2.0 * self.radius),
+                )
+            }
+        };
+        Some(
+            HitRecord::new(p, &self.material, t, u, v, ray, outward_normal)
+                .with_tangents(dpdu, dpdv),
+        )
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(AABB::new(
+            self.base - Vec3::new(self.radius, 0.0, self.radius),
+            self.base + Vec3::new(self.radius, self.height, self.radius),
+        ))
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        let (side, cap) = self.area();
+        surface_pdf_value(self, o, v, side + 2.0 * cap)
+    }
+
+    fn sampling_tolerance(&self) -> f64 {
+        SURFACE_PDF_TOLERANCE
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        let mut rng = sampler::rng();
+        let (side, cap) = self.area();
+        let pick = rng.gen::<f64>() * (side + 2.0 * cap);
+        let phi = 2.0 * f64::consts::PI * rng.gen::<f64>();
+        let (rho, y) = if pick < side {
+            (self.radius, self.height * rng.gen::<f64>())
+        } else {
+            let y = if pick < side + cap { 0.0 } else { self.height };
+            (self.radius * rng.gen::<f64>().sqrt(), y)
+        };
+        self.base + Vec3::new(rho * phi.cos(), y, rho * phi.sin()) - o
+    }
+}
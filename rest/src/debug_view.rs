@@ -0,0 +1,149 @@
+use crate::aov::AovSample;
+use crate::bvh::count_node_visits;
+use crate::hittable::*;
+use crate::integrator::*;
+use crate::ray::Ray;
+use crate::rtweekend::INFINITY;
+use crate::vec3::*;
+
+// Shading modes that show what the renderer sees instead of lighting it, for
+// telling apart broken geometry, normals and materials.
+#[derive(Clone, Copy, PartialEq)]
+pub enum DebugView {
+    // Shading normal of the first hit, mapped from [-1, 1] to [0, 1].
+    Normals,
+    Uv,
+    // Distance to the first hit, white up close fading to black at the scale.
+    Depth,
+    // Vertex weights of triangle hits as red, green and blue; other surfaces
+    // grey.
+    Barycentrics,
+    // A distinct flat colour per material.
+    MaterialId,
+    // Heatmap of the BVH nodes and list entries the camera ray tried.
+    BvhCost,
+    // Heatmap of how many surfaces the path bounced off before ending.
+    Bounces,
+    // Surface colour without any lighting.
+    Albedo,
+}
+
+impl DebugView {
+    pub fn from_name(name: &str) -> DebugView {
+        match name {
+            "normals" => DebugView::Normals,
+            "uv" => DebugView::Uv,
+            "depth" => DebugView::Depth,
+            "barycentrics" => DebugView::Barycentrics,
+            "material-id" => DebugView::MaterialId,
+            "bvh-cost" => DebugView::BvhCost,
+            "bounces" => DebugView::Bounces,
+            "albedo" => DebugView::Albedo,
+            _ => panic!["unknown debug view {}", name],
+        }
+    }
+
+    // Value the depth and heatmap views saturate at, unless overridden.
+    // `diagonal` is the size of the scene.
+    pub fn default_scale(&self, diagonal: f64) -> f64 {
+        match self {
+            DebugView::Depth => 2.0 * diagonal,
+            DebugView::BvhCost => 64.0,
+            DebugView::Bounces => 16.0,
+            _ => 1.0,
+        }
+    }
+}
+
+// Shades camera rays with a debug view, saturating at `scale` where the view
+// has one.
+pub struct DebugShader<'a> {
+    pub view: DebugView,
+    pub scale: f64,
+    pub world: &'a dyn Hittable,
+    pub lights: &'a dyn Hittable,
+    pub background: Color,
+    pub max_depth: i32,
+}
+
+impl<'a> DebugShader<'a> {
+    fn surface_color(&self, r: &Ray, rec: &HitRecord) -> Color {
+        match self.view {
+            DebugView::Normals => {
+                0.5 * (rec.material.shading_normal(rec) + Vec3::new(1.0, 1.0, 1.0))
+            }
+            DebugView::Uv => Color::new(rec.u.fract(), rec.v.fract(), 0.0),
+            DebugView::Depth => {
+                let t = (rec.t * r.direction().length() / self.scale).clamp(0.0, 1.0);
+                Color::new(1.0 - t, 1.0 - t, 1.0 - t)
+            }
+            DebugView::Barycentrics => match rec.barycentrics {
+                Some((u, v)) => Color::new(1.0 - u - v, u, v),
+                None => Color::new(0.2, 0.2, 0.2),
+            },
+            DebugView::MaterialId => id_color(rec.material.id()),
+            _ => rec.material.albedo(rec),
+        }
+    }
+
+    // Surfaces a path hits before escaping, being absorbed or reaching
+    // `max_depth`.
+    fn path_bounces(&self, r: &Ray) -> i32 {
+        let mut bounces = 0;
+        let mut ray = Ray::new(*r.origin(), *r.direction(), r.time());
+        while bounces < self.max_depth {
+            let rec = match self.world.hit(&ray, 0.001, INFINITY) {
+                Some(rec) => rec,
+                None => break,
+            };
+            bounces += 1;
+            match scatter_ray(&ray, &rec, self.lights) {
+                Some((scattered, _)) => ray = scattered,
+                None => break,
+            }
+        }
+        bounces
+    }
+}
+
+impl<'a> Integrator for DebugShader<'a> {
+    fn ray_color(&self, r: &Ray) -> (Color, AovSample, Option<i32>) {
+        let color = match self.view {
+            DebugView::Bounces => heat(self.path_bounces(r) as f64 / self.scale),
+            DebugView::BvhCost => {
+                let (_, visits) = count_node_visits(|| self.world.hit(r, 0.001, INFINITY));
+                heat(visits as f64 / self.scale)
+            }
+            _ => match self.world.hit(r, 0.001, INFINITY) {
+                Some(rec) => self.surface_color(r, &rec),
+                None => Color::default(),
+            },
+        };
+        let color = display(color);
+        let aov = first_hit_aovs(r, self.world, &self.background, &color);
+        (color, aov, None)
+    }
+}
+
+// The film gamma-corrects what it's given, so debug colours are squared to
+// come out as chosen.
+fn display(c: Color) -> Color {
+    &c * &c
+}
+
+// Black through red and yellow to white as `t` goes from 0 to 1, like the
+// sample count heatmap.
+fn heat(t: f64) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |start: f64| (3.0 * t - start).clamp(0.0, 1.0);
+    Color::new(channel(0.0), channel(1.0), channel(2.0))
+}
+
+// Flat colour for an ID, scrambled so neighbouring IDs look unrelated.
+fn id_color(id: usize) -> Color {
+    let n = (id as u64 ^ (id as u64 >> 32)) as u32;
+    let h = n.wrapping_mul(0x9e37_79b9) ^ (n >> 16);
+    let h = h.wrapping_mul(0x85eb_ca6b);
+    let channel = |shift: u32| ((h >> shift) & 0xff) as f64 / 255.0;
+    Color::new(channel(24), channel(16), channel(8))
+}
@@ -0,0 +1,159 @@
+use crate::aov::AovSample;
+use crate::color::luminance;
+use crate::vec3::*;
+use rayon::prelude::*;
+
+// B3-spline taps of the A-trous wavelet.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// Edge-avoiding A-trous wavelet filter (Dammertz et al. 2010). Each pass
+// blurs with a 5x5 kernel whose taps spread twice as far apart as the pass
+// before, weighted down across edges in colour, normal, depth and albedo.
+// Lighting is divided by the albedo first and multiplied back afterwards, so
+// texture detail survives; emission is added back unfiltered.
+pub struct Denoiser {
+    pub iterations: u32,
+    // Colour tolerance, compared after gamma; halved every pass.
+    pub sigma_color: f64,
+    pub sigma_normal: f64,
+    // Depth tolerance relative to the depth and the tap spacing.
+    pub sigma_depth: f64,
+    pub sigma_albedo: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_color: 0.6,
+            sigma_normal: 0.3,
+            sigma_depth: 0.02,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+fn finite_or_zero(c: Color) -> Color {
+    let f = |x: f64| if x.is_finite() { x } else { 0.0 };
+    Color::new(f(c.x()), f(c.y()), f(c.z()))
+}
+
+fn gamma(c: &Color) -> Color {
+    Color::new(
+        c.x().max(0.0).sqrt(),
+        c.y().max(0.0).sqrt(),
+        c.z().max(0.0).sqrt(),
+    )
+}
+
+// Albedo used for demodulation, with near-black channels left alone.
+fn demodulation(albedo: &Color) -> Color {
+    let f = |a: f64| if a < 0.01 { 1.0 } else { a };
+    Color::new(f(albedo.x()), f(albedo.y()), f(albedo.z()))
+}
+
+impl Denoiser {
+    // The colour weights would keep isolated fireflies from being spread out,
+    // so first scale any pixel far brighter than its neighbours down to them.
+    fn suppress_outliers(
+        &self,
+        width: usize,
+        height: usize,
+        pixels: &[AovSample],
+        lighting: &[Color],
+    ) -> Vec<Color> {
+        (0..width * height)
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                let mut sum = 0.0;
+                let mut count = 0;
+                for qy in y.saturating_sub(1)..(y + 2).min(height) {
+                    for qx in x.saturating_sub(1)..(x + 2).min(width) {
+                        let j = qy * width + qx;
+                        if j != i && pixels[j].object_id.is_some() {
+                            sum += luminance(&lighting[j]);
+                            count += 1;
+                        }
+                    }
+                }
+                let limit = 4.0 * sum / count.max(1) as f64;
+                let l = luminance(&lighting[i]);
+                if count > 0 && l > limit && l > 0.0 {
+                    (limit / l) * lighting[i]
+                } else {
+                    lighting[i]
+                }
+            })
+            .collect()
+    }
+
+    // Filters the lighting of `pixels` (rows from top to bottom) and returns
+    // the denoised linear colour of every pixel.
+    pub fn denoise(&self, width: usize, height: usize, pixels: &[AovSample]) -> Vec<Color> {
+        let lighting: Vec<Color> = pixels
+            .iter()
+            .map(|p| &finite_or_zero(p.direct + p.indirect) / &demodulation(&p.albedo))
+            .collect();
+        let mut lighting = self.suppress_outliers(width, height, pixels, &lighting);
+
+        let mut sigma_color = self.sigma_color;
+        for pass in 0..self.iterations {
+            let step = 1 << pass;
+            let input = &lighting;
+            lighting = (0..width * height)
+                .into_par_iter()
+                .map(|i| {
+                    let p = &pixels[i];
+                    if p.object_id.is_none() {
+                        return input[i];
+                    }
+                    let (x, y) = ((i % width) as i64, (i / width) as i64);
+                    let c = gamma(&input[i]);
+                    let mut sum = Color::default();
+                    let mut total = 0.0;
+                    for (dy, ky) in KERNEL.iter().enumerate() {
+                        for (dx, kx) in KERNEL.iter().enumerate() {
+                            let qx = x + (dx as i64 - 2) * step;
+                            let qy = y + (dy as i64 - 2) * step;
+                            if qx < 0 || qy < 0 || qx >= width as i64 || qy >= height as i64 {
+                                continue;
+                            }
+                            let j = qy as usize * width + qx as usize;
+                            let q = &pixels[j];
+                            if q.object_id.is_none() {
+                                continue;
+                            }
+                            let dc = (gamma(&input[j]) - c).length_squared();
+                            let dn = (q.normal - p.normal).length_squared();
+                            let dd = (q.depth - p.depth).abs()
+                                / (self.sigma_depth * p.depth.max(q.depth) * step as f64);
+                            let da = (q.albedo - p.albedo).length_squared();
+                            let w = kx
+                                * ky
+                                * (-dc / (sigma_color * sigma_color)
+                                    - dn / (self.sigma_normal * self.sigma_normal)
+                                    - dd
+                                    - da / (self.sigma_albedo * self.sigma_albedo))
+                                    .exp();
+                            sum += w * input[j];
+                            total += w;
+                        }
+                    }
+                    if total > 0.0 {
+                        sum / total
+                    } else {
+                        input[i]
+                    }
+                })
+                .collect();
+            sigma_color *= 0.5;
+        }
+
+        pixels
+            .iter()
+            .zip(lighting.iter())
+            .map(|(p, l)| (l * &demodulation(&p.albedo)) + finite_or_zero(p.emission))
+            .collect()
+    }
+}
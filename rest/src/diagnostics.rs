@@ -0,0 +1,128 @@
+use crate::vec3::Color;
+use std::collections::HashMap;
+
+// Invalid samples reported in full; the rest are only counted.
+const LOGGED: usize = 20;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Problem {
+    NaN,
+    Infinite,
+    Negative,
+}
+
+impl Problem {
+    // The worst thing wrong with `c`, if anything.
+    pub fn of(c: &Color) -> Option<Problem> {
+        let channels = [c.x(), c.y(), c.z()];
+        if channels.iter().any(|x| x.is_nan()) {
+            Some(Problem::NaN)
+        } else if channels.iter().any(|x| x.is_infinite()) {
+            Some(Problem::Infinite)
+        } else if channels.iter().any(|&x| x < 0.0) {
+            Some(Problem::Negative)
+        } else {
+            None
+        }
+    }
+
+    // Colour marking affected pixels in the debug image.
+    fn marker(&self) -> (i32, i32, i32) {
+        match self {
+            Problem::NaN => (255, 0, 255),
+            Problem::Infinite => (255, 255, 0),
+            Problem::Negative => (0, 255, 255),
+        }
+    }
+}
+
+// A sample that came out NaN, infinite or negative. `bounce` is the path
+// vertex (0 at the first hit) whose emission or throughput first went wrong,
+// or `None` if every vertex looked fine and the product overflowed. BDPT,
+// SPPM and MLT don't report it, so their samples always have `None`.
+#[derive(Clone, Copy)]
+pub struct InvalidSample {
+    pub x: usize,
+    pub y: usize,
+    pub bounce: Option<i32>,
+    pub problem: Problem,
+}
+
+// Invalid samples of a render, in pixel coordinates of the crop window. Only
+// the first few are kept whole, so a scene that goes wrong everywhere costs
+// no more than a counter per problem and a marker per pixel.
+#[derive(Default)]
+pub struct Diagnostics {
+    // Samples per problem, indexed by `Problem as usize`.
+    counts: [usize; 3],
+    first: Vec<InvalidSample>,
+    // Problem of the last invalid sample in each affected pixel.
+    markers: HashMap<(usize, usize), Problem>,
+}
+
+impl Diagnostics {
+    pub fn add(&mut self, s: InvalidSample) {
+        self.counts[s.problem as usize] += 1;
+        if self.first.len() < LOGGED {
+            self.first.push(s);
+        }
+        self.markers.insert((s.x, s.y), s.problem);
+    }
+
+    // Folds in the samples of `other`, which came after these.
+    pub fn merge(&mut self, other: Diagnostics) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+        let room = LOGGED.saturating_sub(self.first.len());
+        self.first.extend(other.first.into_iter().take(room));
+        self.markers.extend(other.markers);
+    }
+
+    // Prints totals per problem and the first few offending samples.
+    pub fn report(&self) {
+        let total: usize = self.counts.iter().sum();
+        if total == 0 {
+            return;
+        }
+        eprintln!(
+            "\nInvalid samples: {} NaN, {} infinite, {} negative",
+            self.counts[Problem::NaN as usize],
+            self.counts[Problem::Infinite as usize],
+            self.counts[Problem::Negative as usize]
+        );
+        for s in self.first.iter() {
+            let bounce = match s.bounce {
+                Some(b) => format!("bounce {}", b),
+                None => String::from("unknown bounce"),
+            };
+            eprintln!("  pixel ({}, {}), {}: {:?}", s.x, s.y, bounce, s.problem);
+        }
+        if total > self.first.len() {
+            eprintln!("  ... and {} more", total - self.first.len());
+        }
+    }
+
+    // `image` darkened, with every pixel that had an invalid sample marked:
+    // magenta for NaN, yellow for infinite, cyan for negative.
+    pub fn debug_image(&self, image: &[Vec<(i32, i32, i32)>]) -> Vec<Vec<(i32, i32, i32)>> {
+        let mut debug: Vec<Vec<(i32, i32, i32)>> = image
+            .iter()
+            .map(|row| row.iter().map(|&(r, g, b)| (r / 4, g / 4, b / 4)).collect())
+            .collect();
+        for (&(x, y), problem) in self.markers.iter() {
+            debug[y][x] = problem.marker();
+        }
+        debug
+    }
+}
+
+// Scales `c` down so no channel exceeds `max`, keeping its hue.
+pub fn clamp_radiance(c: Color, max: f64) -> Color {
+    let largest = c.x().max(c.y()).max(c.z());
+    if largest > max {
+        (max / largest) * c
+    } else {
+        c
+    }
+}
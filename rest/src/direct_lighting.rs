@@ -0,0 +1,65 @@
+use crate::aov::AovSample;
+use crate::diagnostics::Problem;
+use crate::hittable::*;
+use crate::integrator::*;
+use crate::material::*;
+use crate::pdf::PDF;
+use crate::ray::Ray;
+use crate::rtweekend::INFINITY;
+use crate::vec3::*;
+
+// One bounce of light for quick previews: diffuse surfaces sample a point on
+// the `lights` list and see whether it emits toward them, while specular ones
+// follow their one ray to whatever it hits. Emitters missing from `lights`
+// only show up in mirrors and glass.
+pub struct DirectLighting<'a> {
+    pub world: &'a dyn Hittable,
+    pub lights: &'a dyn Hittable,
+    pub background: Color,
+}
+
+impl<'a> Integrator for DirectLighting<'a> {
+    fn ray_color(&self, r: &Ray) -> (Color, AovSample, Option<i32>) {
+        let rec = match self.world.hit(r, 0.001, INFINITY) {
+            Some(rec) => rec,
+            None => return background_sample(&self.background),
+        };
+        let mut aov = surface_aovs(r, &rec);
+        let (scattered, throughput) = match rec.material.scatter(r, &rec) {
+            Some(ScatterRecord::Scatter { attenuation, .. }) => {
+                let light_pdf = PDF::hittable(self.lights, &rec.p);
+                let scattered = Ray::new(rec.p, light_pdf.generate(), r.time());
+                let pdf = light_pdf.value(scattered.direction());
+                let scattering_pdf = rec.material.scattering_pdf(r, &rec, &scattered);
+                if pdf <= 0.0 || scattering_pdf <= 0.0 {
+                    (None, Color::default())
+                } else {
+                    (Some(scattered), attenuation * (scattering_pdf / pdf))
+                }
+            }
+            Some(ScatterRecord::Specular {
+                specular_ray,
+                attenuation,
+            }) => (Some(specular_ray), attenuation),
+            None => (None, Color::default()),
+        };
+        let mut invalid_at = None;
+        if let Some(scattered) = scattered {
+            let incoming = match self.world.hit(&scattered, 0.001, INFINITY) {
+                Some(light) => light.material.emitted(&scattered, &light),
+                None => self.background,
+            };
+            aov.direct = &throughput * &incoming;
+            if Problem::of(&incoming).is_some() {
+                invalid_at = Some(1);
+            }
+            if Problem::of(&throughput).is_some() {
+                invalid_at = Some(0);
+            }
+        }
+        if Problem::of(&aov.emission).is_some() {
+            invalid_at = Some(0);
+        }
+        (aov.emission + aov.direct, aov, invalid_at)
+    }
+}
@@ -0,0 +1,105 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::material::Material;
+use crate::onb::ONB;
+use crate::ray::Ray;
+use crate::sampler;
+use crate::vec3::*;
+use rand::Rng;
+use std::f64;
+
+// Flat disk of `radius` around `center`, facing `normal`.
+#[derive(Clone)]
+pub struct Disk<M: Material> {
+    center: Point3,
+    radius: f64,
+    normal: Vec3,
+    // In-plane axes; u is measured from `axis_u` towards `axis_v`.
+    axis_u: Vec3,
+    axis_v: Vec3,
+    material: M,
+}
+
+impl<M: Material> Disk<M> {
+    #[allow(dead_code)]
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: M) -> Self {
+        let frame = ONB::build_from_w(&normal);
+        Disk {
+            center,
+            radius,
+            normal: frame.w(),
+            axis_u: frame.u(),
+            axis_v: frame.v(),
+            material,
+        }
+    }
+}
+
+impl<M: Material> Hittable for Disk<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let normal = self.normal;
+        let denom = dot(&normal, ray.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = dot(&normal, &(self.center - ray.origin())) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = ray.at(t);
+        let offset = p - self.center;
+        let x = dot(&offset, &self.axis_u);
+        let y = dot(&offset, &self.axis_v);
+        let rho = (x * x + y * y).sqrt();
+        if rho > self.radius {
+            return None;
+        }
+
+        // u runs around the rim, v outward from the center.
+        let phi = y.atan2(x);
+        let u = (phi + f64::consts::PI) / (2.0 * f64::consts::PI);
+        let v = rho / self.radius;
+        let radial = if rho > 0.0 {
+            (x / rho) * self.axis_u + (y / rho) * self.axis_v
+        } else {
+            Vec3::default()
+        };
+        let dpdu = 2.0 * f64::consts::PI * rho * cross(&radial, &normal);
+        let dpdv = self.radius * radial;
+        Some(HitRecord::new(p, &self.material, t, u, v, ray, normal).with_tangents(dpdu, dpdv))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        // Extent of a disk along each axis is radius * sqrt(1 - n_axis^2).
+        let n = self.normal;
+        let mut extent = Vec3::default();
+        for a in 0..3 {
+            extent[a] = (self.radius * (1.0 - n[a] * n[a]).max(0.0).sqrt()).max(0.0001);
+        }
+        Some(AABB::new(self.center - extent, self.center + extent))
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        if let Some(hit) = self.hit(&Ray::new(*o, *v, 0.0), 0.001, f64::MAX) {
+            let area = f64::consts::PI * self.radius * self.radius;
+            let distance_squared = hit.t.powi(2) * v.length_squared();
+            let cosine = dot(v, &self.normal).abs() / v.length();
+            if cosine > 1e-8 {
+                distance_squared / (cosine * area)
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        let mut rng = sampler::rng();
+        let rho = self.radius * rng.gen::<f64>().sqrt();
+        let phi = 2.0 * f64::consts::PI * rng.gen::<f64>();
+        let p = self.center + rho * phi.cos() * self.axis_u + rho * phi.sin() * self.axis_v;
+        p - o
+    }
+}
@@ -0,0 +1,303 @@
+use crate::color::get_color;
+use crate::vec3::*;
+use std::f64::consts::PI;
+use std::sync::Mutex;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum Filter {
+    Box { radius: f64 },
+    Tent { radius: f64 },
+    // `alpha` sets the falloff; the curve is shifted down to reach zero at
+    // the radius.
+    Gaussian { radius: f64, alpha: f64 },
+    // Mitchell-Netravali cubic with the usual B and C parameters.
+    Mitchell { radius: f64, b: f64, c: f64 },
+    // Sinc windowed by a wider sinc, `tau` lobes wide.
+    Lanczos { radius: f64, tau: f64 },
+}
+
+fn mitchell_1d(x: f64, b: f64, c: f64) -> f64 {
+    let x = (2.0 * x).abs();
+    if x > 1.0 {
+        ((-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b))
+            / 6.0
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    let x = x.abs();
+    if x < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl Filter {
+    // Filter by name with its customary radius.
+    pub fn from_name(name: &str) -> Filter {
+        match name {
+            "box" => Filter::Box { radius: 0.5 },
+            "tent" => Filter::Tent { radius: 1.0 },
+            "gaussian" => Filter::Gaussian {
+                radius: 1.5,
+                alpha: 2.0,
+            },
+            "mitchell" => Filter::Mitchell {
+                radius: 2.0,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            "lanczos" => Filter::Lanczos {
+                radius: 3.0,
+                tau: 3.0,
+            },
+            _ => panic!["unknown filter {}", name],
+        }
+    }
+
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius, .. } => radius,
+        }
+    }
+
+    pub fn with_radius(self, r: f64) -> Filter {
+        match self {
+            Filter::Box { .. } => Filter::Box { radius: r },
+            Filter::Tent { .. } => Filter::Tent { radius: r },
+            Filter::Gaussian { alpha, .. } => Filter::Gaussian { radius: r, alpha },
+            Filter::Mitchell { b, c, .. } => Filter::Mitchell { radius: r, b, c },
+            Filter::Lanczos { tau, .. } => Filter::Lanczos { radius: r, tau },
+        }
+    }
+
+    // Weight of a sample at offset (x, y) from a pixel centre.
+    pub fn evaluate(&self, x: f64, y: f64) -> f64 {
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => (radius - x.abs()).max(0.0) * (radius - y.abs()).max(0.0),
+            Filter::Gaussian { radius, alpha } => {
+                let g =
+                    |d: f64| ((-alpha * d * d).exp() - (-alpha * radius * radius).exp()).max(0.0);
+                g(x) * g(y)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                mitchell_1d(x / radius, b, c) * mitchell_1d(y / radius, b, c)
+            }
+            Filter::Lanczos { radius, tau } => {
+                let w = |d: f64| {
+                    if d.abs() > radius {
+                        0.0
+                    } else {
+                        sinc(d) * sinc(d / tau)
+                    }
+                };
+                w(x) * w(y)
+            }
+        }
+    }
+}
+
+// Filter weights over one quadrant, so splatting doesn't re-evaluate the
+// filter for every pixel a sample touches.
+const TABLE_SIZE: usize = 64;
+
+#[derive(Clone, Copy, Default)]
+struct FilmPixel {
+    sum: Color,
+    weight: f64,
+}
+
+// Accumulates filtered samples over the crop window of the image. Each
+// sample is splatted onto every pixel whose centre lies within the filter
+// radius, and pixels end up as their weighted average.
+pub struct Film {
+    // Pixels being rendered, [x0, x1) by [y0, y1), with y running down.
+    pub x0: usize,
+    pub x1: usize,
+    pub y0: usize,
+    pub y1: usize,
+    radius: f64,
+    table: Vec<f64>,
+    pixels: Vec<FilmPixel>,
+    // Light-tracing contributions, which land anywhere on the image rather
+    // than around the sample being taken. Summed unfiltered and added to the
+    // pixels scaled by `splat_scale`.
+    splats: Mutex<Vec<Color>>,
+    splat_scale: f64,
+}
+
+// Part of a film covering whole rows, filled by one thread and merged back
+// with `Film::merge`.
+pub struct FilmTile {
+    y0: usize,
+    y1: usize,
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    // `crop` is (x0, x1, y0, y1) as fractions of the `width` by `height`
+    // image, y running down.
+    pub fn new(width: usize, height: usize, filter: Filter, crop: (f64, f64, f64, f64)) -> Self {
+        let x0 = (crop.0 * width as f64).ceil() as usize;
+        let x1 = ((crop.1 * width as f64).ceil() as usize).min(width);
+        let y0 = (crop.2 * height as f64).ceil() as usize;
+        let y1 = ((crop.3 * height as f64).ceil() as usize).min(height);
+        if x0 >= x1 || y0 >= y1 {
+            panic!["crop window {:?} is empty", crop]
+        }
+        let radius = filter.radius();
+        let mut table = Vec::with_capacity(TABLE_SIZE * TABLE_SIZE);
+        for y in 0..TABLE_SIZE {
+            for x in 0..TABLE_SIZE {
+                table.push(filter.evaluate(
+                    (x as f64 + 0.5) / TABLE_SIZE as f64 * radius,
+                    (y as f64 + 0.5) / TABLE_SIZE as f64 * radius,
+                ));
+            }
+        }
+        Film {
+            x0,
+            x1,
+            y0,
+            y1,
+            radius,
+            table,
+            pixels: vec![FilmPixel::default(); (x1 - x0) * (y1 - y0)],
+            splats: Mutex::new(vec![Color::default(); (x1 - x0) * (y1 - y0)]),
+            splat_scale: 0.0,
+        }
+    }
+
+    pub fn crop_width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn crop_height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    // Tile for the samples of image row `y`: every row they can reach.
+    pub fn tile(&self, y: usize) -> FilmTile {
+        let reach = self.radius.ceil() as usize;
+        let y0 = y.saturating_sub(reach).max(self.y0);
+        let y1 = (y + reach + 1).min(self.y1);
+        FilmTile {
+            y0,
+            y1,
+            pixels: vec![FilmPixel::default(); self.crop_width() * (y1 - y0)],
+        }
+    }
+
+    pub fn merge(&mut self, tile: FilmTile) {
+        let offset = (tile.y0 - self.y0) * self.crop_width();
+        for (i, p) in tile.pixels.iter().enumerate() {
+            let pixel = &mut self.pixels[offset + i];
+            pixel.sum += p.sum;
+            pixel.weight += p.weight;
+        }
+    }
+
+    // Empty buffer covering the crop window, for one thread's splats.
+    pub fn splat_buffer(&self) -> Vec<Color> {
+        vec![Color::default(); self.pixels.len()]
+    }
+
+    // Adds `radiance` to the pixel of `buffer` containing image position
+    // (x, y), y running down. Splats outside the crop window are dropped.
+    pub fn add_splat(&self, buffer: &mut [Color], x: f64, y: f64, radiance: &Color) {
+        if !(radiance.x().is_finite() && radiance.y().is_finite() && radiance.z().is_finite()) {
+            return;
+        }
+        if x < self.x0 as f64 || x >= self.x1 as f64 || y < self.y0 as f64 || y >= self.y1 as f64 {
+            return;
+        }
+        let i = (y as usize - self.y0) * self.crop_width() + x as usize - self.x0;
+        buffer[i] += *radiance;
+    }
+
+    pub fn merge_splats(&self, buffer: &[Color]) {
+        let mut splats = self.splats.lock().unwrap();
+        for (s, b) in splats.iter_mut().zip(buffer) {
+            *s += *b;
+        }
+    }
+
+    // Light tracing estimates the whole image from every path, so splats are
+    // scaled by the pixel count over the number of paths traced.
+    pub fn set_splat_scale(&mut self, scale: f64) {
+        self.splat_scale = scale;
+    }
+
+    // Linear radiance of the crop window, row by row from the top. Pixels
+    // the filter gave no positive weight only get their splats.
+    pub fn radiance(&self) -> Vec<Color> {
+        let splats = self.splats.lock().unwrap();
+        self.pixels
+            .iter()
+            .zip(splats.iter())
+            .map(|(p, s)| {
+                let filtered = if p.weight > 0.0 {
+                    p.sum / p.weight
+                } else {
+                    Color::default()
+                };
+                filtered + self.splat_scale * *s
+            })
+            .collect()
+    }
+
+    // Gamma-corrected 8-bit pixels of the crop window, rows from top to
+    // bottom.
+    pub fn image(&self) -> Vec<Vec<(i32, i32, i32)>> {
+        self.radiance()
+            .iter()
+            .map(|c| get_color(*c, 1))
+            .collect::<Vec<_>>()
+            .chunks(self.crop_width())
+            .map(|row| row.to_vec())
+            .collect()
+    }
+
+    // Adds to `tile` a sample taken at continuous image position (x, y), y running
+    // down, with pixel (i, j) covering [i, i + 1) by [j, j + 1).
+    pub fn add_sample(&self, tile: &mut FilmTile, x: f64, y: f64, radiance: &Color) {
+        // A NaN or infinity would spread to every pixel the filter touches.
+        if !(radiance.x().is_finite() && radiance.y().is_finite() && radiance.z().is_finite()) {
+            return;
+        }
+        let r = self.radius;
+        let px0 = ((x - 0.5 - r).floor() + 1.0).max(self.x0 as f64) as usize;
+        let px1 = ((x - 0.5 + r).floor() + 1.0).min(self.x1 as f64);
+        let py0 = ((y - 0.5 - r).floor() + 1.0).max(tile.y0 as f64) as usize;
+        let py1 = ((y - 0.5 + r).floor() + 1.0).min(tile.y1 as f64);
+        if px1 <= px0 as f64 || py1 <= py0 as f64 {
+            return;
+        }
+        let index = |d: f64| ((d.abs() / r * TABLE_SIZE as f64) as usize).min(TABLE_SIZE - 1);
+        for py in py0..py1 as usize {
+            let ty = index(py as f64 + 0.5 - y);
+            for px in px0..px1 as usize {
+                let tx = index(px as f64 + 0.5 - x);
+                let weight = self.table[ty * TABLE_SIZE + tx];
+                let pixel = &mut tile.pixels[(py - tile.y0) * self.crop_width() + px - self.x0];
+                pixel.sum += weight * *radiance;
+                pixel.weight += weight;
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use crate::color::luminance;
+use crate::hittable::HitRecord;
+use crate::material::*;
+use crate::ray::Ray;
+use crate::rtweekend::{degrees_to_radians, random_double};
+use crate::vec3::*;
+use std::f64::consts::{LN_2, PI};
+
+// Scattering lobes modelled explicitly: R, TT and TRT. Everything after is
+// lumped into one extra, isotropic-in-azimuth lobe.
+const P_MAX: usize = 3;
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0.0).sqrt()
+}
+
+fn safe_asin(x: f64) -> f64 {
+    x.clamp(-1.0, 1.0).asin()
+}
+
+fn exp(c: &Vec3) -> Vec3 {
+    Vec3::new(c.x().exp(), c.y().exp(), c.z().exp())
+}
+
+// Unpolarised Fresnel reflectance of a dielectric, entering from outside.
+fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 {
+        (-cos_theta_i, 1.0 / eta)
+    } else {
+        (cos_theta_i, eta)
+    };
+    let sin_theta_t = safe_sqrt(1.0 - cos_theta_i * cos_theta_i) / eta;
+    if sin_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+    let parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+// Modified Bessel function of the first kind, order zero.
+fn i0(x: f64) -> f64 {
+    let mut val = 0.0;
+    let mut x2i = 1.0;
+    let mut ifact = 1.0;
+    let mut i4 = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            ifact *= i as f64;
+        }
+        val += x2i / (i4 * ifact * ifact);
+        x2i *= x * x;
+        i4 *= 4.0;
+    }
+    val
+}
+
+fn log_i0(x: f64) -> f64 {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+// Longitudinal scattering function.
+fn mp(cos_theta_i: f64, cos_theta_o: f64, sin_theta_i: f64, sin_theta_o: f64, v: f64) -> f64 {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        // Evaluated in log space to avoid overflow for smooth fibres.
+        (log_i0(a) - b - 1.0 / v + LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        ((-b).exp() * i0(a)) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+fn logistic(x: f64, s: f64) -> f64 {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+fn trimmed_logistic(x: f64, s: f64, a: f64, b: f64) -> f64 {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64, a: f64, b: f64) -> f64 {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(a, s)) - 1.0).ln();
+    x.clamp(a, b)
+}
+
+// Net azimuthal deflection of lobe `p`.
+fn phi(p: usize, gamma_o: f64, gamma_t: f64) -> f64 {
+    2.0 * p as f64 * gamma_t - 2.0 * gamma_o + p as f64 * PI
+}
+
+// Azimuthal scattering function.
+fn np(phi_diff: f64, p: usize, s: f64, gamma_o: f64, gamma_t: f64) -> f64 {
+    let mut dphi = phi_diff - phi(p, gamma_o, gamma_t);
+    while dphi > PI {
+        dphi -= 2.0 * PI;
+    }
+    while dphi < -PI {
+        dphi += 2.0 * PI;
+    }
+    trimmed_logistic(dphi, s, -PI, PI)
+}
+
+// Attenuation of each lobe from Fresnel reflection and absorption `t` along
+// one pass through the fibre.
+fn ap(cos_theta_o: f64, eta: f64, h: f64, t: &Vec3) -> [Vec3; P_MAX + 1] {
+    let cos_gamma_o = safe_sqrt(1.0 - h * h);
+    let f = fresnel_dielectric(cos_theta_o * cos_gamma_o, eta);
+    let mut ap = [Vec3::default(); P_MAX + 1];
+    ap[0] = Vec3::new(f, f, f);
+    ap[1] = (1.0 - f) * (1.0 - f) * *t;
+    for p in 2..P_MAX {
+        ap[p] = f * (&ap[p - 1] * t);
+    }
+    let tf = f * *t;
+    ap[P_MAX] = &(f * (&ap[P_MAX - 1] * t)) / &(Vec3::new(1.0, 1.0, 1.0) - tf);
+    ap
+}
+
+// Physically based hair fibre after d'Eon et al. and Chiang et al.: a rough
+// dielectric cylinder whose interior absorbs light. Put it on `Cylinder`
+// curves, which supply the offset across the fibre through `v`, the fibre
+// direction as `dpdu` and a normal that curls around the tube.
+pub struct Hair {
+    sigma_a: Vec3,
+    eta: f64,
+    // Longitudinal variance of each lobe.
+    v: [f64; P_MAX + 1],
+    // Azimuthal logistic scale.
+    s: f64,
+    // Cuticle scale tilt rotations for the R, TT and TRT lobes.
+    sin_2k_alpha: [f64; 3],
+    cos_2k_alpha: [f64; 3],
+    id: usize,
+}
+
+impl Hair {
+    // `beta_m` and `beta_n` are the longitudinal and azimuthal roughness in
+    // [0, 1]; `alpha` tilts the cuticle scales, in degrees (about 2 for human
+    // hair).
+    #[allow(dead_code)]
+    pub fn new(sigma_a: Vec3, beta_m: f64, beta_n: f64, alpha: f64) -> Self {
+        let v0 = 0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20);
+        let v0 = v0 * v0;
+        let mut v = [4.0 * v0; P_MAX + 1];
+        v[0] = v0;
+        v[1] = 0.25 * v0;
+        let s =
+            (PI / 8.0).sqrt() * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22));
+
+        let mut sin_2k_alpha = [0.0; 3];
+        let mut cos_2k_alpha = [0.0; 3];
+        sin_2k_alpha[0] = degrees_to_radians(alpha).sin();
+        cos_2k_alpha[0] = safe_sqrt(1.0 - sin_2k_alpha[0] * sin_2k_alpha[0]);
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1] * cos_2k_alpha[i - 1]
+                - sin_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+        }
+
+        Hair {
+            sigma_a,
+            eta: 1.55,
+            v,
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha,
+            id: next_material_id(),
+        }
+    }
+
+    // Absorption from the concentration of eumelanin (brown to black) and
+    // pheomelanin (red). Eumelanin around 0.3 gives blonde hair, 1.3 brown
+    // and 8 black.
+    #[allow(dead_code)]
+    pub fn from_melanin(
+        eumelanin: f64,
+        pheomelanin: f64,
+        beta_m: f64,
+        beta_n: f64,
+        alpha: f64,
+    ) -> Self {
+        let sigma_a =
+            eumelanin * Vec3::new(0.419, 0.697, 1.37) + pheomelanin * Vec3::new(0.187, 0.4, 1.05);
+        Hair::new(sigma_a, beta_m, beta_n, alpha)
+    }
+
+    #[allow(dead_code)]
+    pub fn with_eta(mut self, eta: f64) -> Self {
+        self.eta = eta;
+        self
+    }
+
+    // sin and cos of theta_o after tilting for the scales of lobe `p`.
+    fn tilt(&self, p: usize, sin_theta_o: f64, cos_theta_o: f64) -> (f64, f64) {
+        let (s, c) = match p {
+            0 => (-self.sin_2k_alpha[1], self.cos_2k_alpha[1]),
+            1 => (self.sin_2k_alpha[0], self.cos_2k_alpha[0]),
+            2 => (self.sin_2k_alpha[2], self.cos_2k_alpha[2]),
+            _ => return (sin_theta_o, cos_theta_o),
+        };
+        (
+            sin_theta_o * c + cos_theta_o * s,
+            (cos_theta_o * c - sin_theta_o * s).abs(),
+        )
+    }
+
+    // Transmittance of one pass through the fibre, and the refracted
+    // azimuthal offset gamma_t.
+    fn transmittance(&self, sin_theta_o: f64, cos_theta_o: f64, h: f64) -> (Vec3, f64) {
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let sin_gamma_t = h / etap;
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t * sin_gamma_t);
+        let t = exp(&(-(2.0 * cos_gamma_t / cos_theta_t) * self.sigma_a));
+        (t, safe_asin(sin_gamma_t))
+    }
+
+    // Probability of picking each lobe when sampling.
+    fn ap_pdf(&self, sin_theta_o: f64, cos_theta_o: f64, h: f64) -> [f64; P_MAX + 1] {
+        let (t, _) = self.transmittance(sin_theta_o, cos_theta_o, h);
+        let ap = ap(cos_theta_o, self.eta, h, &t);
+        let total: f64 = ap.iter().map(luminance).sum();
+        let mut pdf = [0.0; P_MAX + 1];
+        for p in 0..=P_MAX {
+            pdf[p] = luminance(&ap[p]) / total;
+        }
+        pdf
+    }
+
+    // The BSDF times |cos theta_i| for local directions (x along the fibre),
+    // and the pdf of sampling `wi` with `sample`.
+    fn eval(&self, wo: &Vec3, wi: &Vec3, h: f64) -> (Vec3, f64) {
+        let sin_theta_o = wo.x();
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z().atan2(wo.y());
+        let sin_theta_i = wi.x();
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+        let phi_i = wi.z().atan2(wi.y());
+        let gamma_o = safe_asin(h);
+
+        let (t, gamma_t) = self.transmittance(sin_theta_o, cos_theta_o, h);
+        let ap = ap(cos_theta_o, self.eta, h, &t);
+        let ap_pdf = self.ap_pdf(sin_theta_o, cos_theta_o, h);
+        let phi_diff = phi_i - phi_o;
+
+        let mut f = Vec3::default();
+        let mut pdf = 0.0;
+        for p in 0..=P_MAX {
+            let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+            let m = mp(
+                cos_theta_i,
+                cos_theta_op,
+                sin_theta_i,
+                sin_theta_op,
+                self.v[p],
+            );
+            let n = if p < P_MAX {
+                np(phi_diff, p, self.s, gamma_o, gamma_t)
+            } else {
+                1.0 / (2.0 * PI)
+            };
+            f += m * n * ap[p];
+            pdf += m * n * ap_pdf[p];
+        }
+        (f, pdf)
+    }
+
+    fn sample(&self, wo: &Vec3, h: f64) -> Vec3 {
+        let sin_theta_o = wo.x();
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z().atan2(wo.y());
+        let gamma_o = safe_asin(h);
+
+        // Pick a lobe in proportion to its attenuation.
+        let ap_pdf = self.ap_pdf(sin_theta_o, cos_theta_o, h);
+        let mut u = random_double();
+        let mut p = 0;
+        while p < P_MAX && u >= ap_pdf[p] {
+            u -= ap_pdf[p];
+            p += 1;
+        }
+
+        // Sample the longitudinal function.
+        let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+        let u = random_double().max(1e-5);
+        let v = self.v[p];
+        let cos_theta = 1.0 + v * (u + (1.0 - u) * (-2.0 / v).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let cos_phi = (2.0 * PI * random_double()).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+
+        // Then the azimuthal one.
+        let (_, gamma_t) = self.transmittance(sin_theta_o, cos_theta_o, h);
+        let dphi = if p < P_MAX {
+            phi(p, gamma_o, gamma_t) + sample_trimmed_logistic(random_double(), self.s, -PI, PI)
+        } else {
+            2.0 * PI * random_double()
+        };
+        let phi_i = phi_o + dphi;
+        Vec3::new(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        )
+    }
+}
+
+impl Material for Hair {
+    // Colour of light passing straight through the middle of the fibre.
+    fn albedo(&self, _hit: &HitRecord) -> Color {
+        exp(&(-2.0 * self.sigma_a))
+    }
+
+    // The lobes are importance sampled almost exactly, so the sampled ray is
+    // returned directly with its weight rather than mixed with light
+    // sampling.
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let x = unit_vector(&rec.dpdu);
+        let z = rec.shading_normal;
+        let y = cross(&z, &x);
+        let to_local = |d: &Vec3| Vec3::new(dot(d, &x), dot(d, &y), dot(d, &z));
+
+        let h = (2.0 * rec.v - 1.0).clamp(-0.9999, 0.9999);
+        let wo = to_local(&-unit_vector(r_in.direction()));
+        let wi = self.sample(&wo, h);
+        let (f, pdf) = self.eval(&wo, &wi, h);
+        if pdf <= 0.0 || !pdf.is_finite() {
+            return None;
+        }
+        let direction = wi.x() * x + wi.y() * y + wi.z() * z;
+        Some(ScatterRecord::Specular {
+            specular_ray: Ray::new(rec.p, direction, r_in.time()),
+            attenuation: f / pdf,
+        })
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
@@ -1,15 +1,30 @@
 use crate::aabb::*;
 use crate::material::*;
+use crate::onb::ONB;
 use crate::ray::Ray;
 use crate::vec3::*;
+
+#[derive(Clone)]
 pub struct HitRecord<'a> {
     pub p: Point3,
+    // Geometric normal, facing against the incoming ray.
     pub normal: Vec3,
+    // Normal used for shading; equals `normal` unless a material perturbs it.
+    pub shading_normal: Vec3,
+    // Surface derivatives with respect to the texture coordinates.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
     pub material: &'a dyn Material,
     pub t: f64,
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    // Index of the hit object in the outermost `HittableList`, for the object
+    // ID AOV. Nested lists and BVHs don't renumber it, so every primitive
+    // inside one of them shares its entry's ID.
+    pub object_id: usize,
+    // Weights of the second and third vertices, for hits on triangles.
+    pub barycentrics: Option<(f64, f64)>,
 }
 
 impl<'a> HitRecord<'a> {
@@ -23,15 +38,46 @@ impl<'a> HitRecord<'a> {
         outward_normal: Vec3,
     ) -> Self {
         let (front_face, normal) = calc_face_normal(r, outward_normal);
+        // Arbitrary tangent frame until the primitive supplies real derivatives.
+        let uvw = ONB::build_from_w(&normal);
         Self {
             p,
             normal,
+            shading_normal: normal,
+            dpdu: uvw.u(),
+            dpdv: uvw.v(),
             material,
             t,
             u,
             v,
             front_face,
+            object_id: 0,
+            barycentrics: None,
+        }
+    }
+
+    pub fn with_tangents(mut self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        if !dpdu.near_zero() && !dpdv.near_zero() {
+            self.dpdu = dpdu;
+            self.dpdv = dpdv;
+        }
+        self
+    }
+
+    // Orthonormal shading frame (tangent, bitangent) around the shading normal.
+    pub fn tangent_frame(&self) -> (Vec3, Vec3) {
+        let n = &self.shading_normal;
+        let t = self.dpdu - dot(n, &self.dpdu) * n;
+        if t.near_zero() {
+            let uvw = ONB::build_from_w(n);
+            return (uvw.u(), uvw.v());
+        }
+        let t = unit_vector(&t);
+        let mut b = cross(n, &t);
+        if dot(&b, &self.dpdv) < 0.0 {
+            b = -b;
         }
+        (t, b)
     }
 }
 
@@ -44,6 +90,11 @@ pub trait Hittable: Send + Sync {
     fn random(&self, _o: &Vec3) -> Vec3 {
         Vec3::new(1.0, 0.0, 0.0)
     }
+    // Fraction of `random` directions `validate_lights` lets get a zero
+    // `pdf_value`.
+    fn sampling_tolerance(&self) -> f64 {
+        0.0
+    }
 }
 
 fn calc_face_normal(r: &Ray, outward_normal: Vec3) -> (bool, Vec3) {
@@ -79,4 +130,16 @@ impl<H: Hittable> Hittable for FlipNormals<H> {
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
         self.hittable.bounding_box(t0, t1)
     }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        self.hittable.pdf_value(o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        self.hittable.random(o)
+    }
+
+    fn sampling_tolerance(&self) -> f64 {
+        self.hittable.sampling_tolerance()
+    }
 }
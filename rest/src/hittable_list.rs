@@ -1,6 +1,8 @@
 use crate::aabb::*;
+use crate::bvh::add_node_visits;
 use crate::hittable::*;
 use crate::ray::Ray;
+use crate::sampler;
 use crate::vec3::*;
 use rand::seq::SliceRandom;
 
@@ -24,10 +26,13 @@ impl Hittable for HittableList {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let mut closest_so_far = t_max;
         let mut rec_opt: Option<HitRecord> = None;
+        // A list tries every object, so each counts as a visit.
+        add_node_visits(self.objects.len() as u64);
 
-        self.objects.iter().for_each(|object| {
-            if let Some(rec) = object.hit(r, t_min, closest_so_far) {
+        self.objects.iter().enumerate().for_each(|(i, object)| {
+            if let Some(mut rec) = object.hit(r, t_min, closest_so_far) {
                 closest_so_far = rec.t;
+                rec.object_id = i;
                 rec_opt = Some(rec);
             }
         });
@@ -54,9 +59,13 @@ impl Hittable for HittableList {
     }
 
     fn random(&self, o: &Vec3) -> Vec3 {
+        self.objects.choose(&mut sampler::rng()).unwrap().random(o)
+    }
+
+    fn sampling_tolerance(&self) -> f64 {
         self.objects
-            .choose(&mut rand::thread_rng())
-            .unwrap()
-            .random(o)
+            .iter()
+            .map(|h| h.sampling_tolerance())
+            .fold(0.0, f64::max)
     }
 }
@@ -0,0 +1,57 @@
+use crate::aabb::AABB;
+use crate::hittable::*;
+use crate::material::Material;
+use crate::onb::ONB;
+use crate::ray::Ray;
+use crate::vec3::*;
+
+// Unbounded plane through `point`, for ground planes. It has no bounding
+// box, so keep it in a `HittableList` rather than a `BVH`. The uv
+// coordinates are distances along two axes in the plane.
+#[derive(Clone)]
+pub struct InfinitePlane<M: Material> {
+    point: Point3,
+    normal: Vec3,
+    axis_u: Vec3,
+    axis_v: Vec3,
+    material: M,
+}
+
+impl<M: Material> InfinitePlane<M> {
+    #[allow(dead_code)]
+    pub fn new(point: Point3, normal: Vec3, material: M) -> Self {
+        let frame = ONB::build_from_w(&normal);
+        InfinitePlane {
+            point,
+            normal: frame.w(),
+            axis_u: frame.u(),
+            axis_v: frame.v(),
+            material,
+        }
+    }
+}
+
+impl<M: Material> Hittable for InfinitePlane<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = dot(&self.normal, ray.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = dot(&self.normal, &(self.point - ray.origin())) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let p = ray.at(t);
+        let local = p - self.point;
+        let u = dot(&local, &self.axis_u);
+        let v = dot(&local, &self.axis_v);
+        Some(
+            HitRecord::new(p, &self.material, t, u, v, ray, self.normal)
+                .with_tangents(self.axis_u, self.axis_v),
+        )
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        None
+    }
+}
@@ -0,0 +1,170 @@
+use crate::aov::AovSample;
+use crate::diagnostics::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::pdf::PDF;
+use crate::ray::Ray;
+use crate::rtweekend::INFINITY;
+use crate::vec3::*;
+
+// Light transport algorithms that shade one camera ray at a time, drawing
+// every random number from `sampler::rng()` so MLT can drive them too.
+pub trait Integrator: Send + Sync {
+    // Radiance arriving along camera ray `r`, the AOVs of its first hit, and
+    // the first path vertex, 0 being that hit, whose contribution was NaN,
+    // infinite or negative.
+    fn ray_color(&self, r: &Ray) -> (Color, AovSample, Option<i32>);
+}
+
+// Light leaving the first surface a ray hits, split into what the surface
+// emits and what it reflects.
+struct Radiance {
+    emitted: Color,
+    reflected: Color,
+    // First path vertex, 0 being this surface, whose emission or throughput
+    // was NaN, infinite or negative.
+    invalid_at: Option<i32>,
+}
+
+impl Radiance {
+    fn total(&self) -> Color {
+        self.emitted + self.reflected
+    }
+}
+
+// Unidirectional path tracer, sampling a mixture of the lights and the
+// material at every bounce. With `clamp`, light reaching the first hit after
+// more than one bounce is limited to that much per channel, trading a little
+// energy for fewer fireflies.
+pub struct PathTracer<'a> {
+    pub world: &'a dyn Hittable,
+    pub lights: &'a dyn Hittable,
+    pub background: Color,
+    pub max_depth: i32,
+    pub clamp: Option<f64>,
+}
+
+impl<'a> PathTracer<'a> {
+    fn trace(&self, r: &Ray, depth: i32) -> Radiance {
+        let mut radiance = Radiance {
+            emitted: Color::default(),
+            reflected: Color::default(),
+            invalid_at: None,
+        };
+        // If we've exceeded the ray bounce limit, no more light is gathered.
+        if depth <= 0 {
+            return radiance;
+        }
+
+        if let Some(rec) = self.world.hit(r, 0.001, INFINITY) {
+            radiance.emitted = rec.material.emitted(r, &rec);
+            if let Some((scattered, throughput)) = scatter_ray(r, &rec, self.lights) {
+                let incoming = self.trace(&scattered, depth - 1);
+                radiance.reflected = &throughput * &incoming.total();
+                radiance.invalid_at = incoming.invalid_at.map(|b| b + 1);
+                if Problem::of(&throughput).is_some() {
+                    radiance.invalid_at = Some(0);
+                }
+            }
+            if Problem::of(&radiance.emitted).is_some() {
+                radiance.invalid_at = Some(0);
+            }
+            return radiance;
+        }
+        radiance.emitted = self.background;
+        radiance
+    }
+}
+
+impl<'a> Integrator for PathTracer<'a> {
+    fn ray_color(&self, r: &Ray) -> (Color, AovSample, Option<i32>) {
+        if self.max_depth <= 0 {
+            return (Color::default(), AovSample::default(), None);
+        }
+        let rec = match self.world.hit(r, 0.001, INFINITY) {
+            Some(rec) => rec,
+            None => return background_sample(&self.background),
+        };
+        let mut aov = surface_aovs(r, &rec);
+        let mut invalid_at = None;
+        if let Some((scattered, throughput)) = scatter_ray(r, &rec, self.lights) {
+            let incoming = self.trace(&scattered, self.max_depth - 1);
+            aov.direct = &throughput * &incoming.emitted;
+            aov.indirect = &throughput * &incoming.reflected;
+            if let Some(max) = self.clamp {
+                aov.indirect = clamp_radiance(aov.indirect, max);
+            }
+            invalid_at = incoming.invalid_at.map(|b| b + 1);
+            if Problem::of(&throughput).is_some() {
+                invalid_at = Some(0);
+            }
+        }
+        if Problem::of(&aov.emission).is_some() {
+            invalid_at = Some(0);
+        }
+        (aov.emission + aov.direct + aov.indirect, aov, invalid_at)
+    }
+}
+
+// Picks the next ray of the path at `rec`, with the throughput along it.
+pub fn scatter_ray(r: &Ray, rec: &HitRecord, lights: &dyn Hittable) -> Option<(Ray, Color)> {
+    match rec.material.scatter(r, rec)? {
+        ScatterRecord::Scatter { pdf, attenuation } => {
+            let light_pdf = PDF::hittable(lights, &rec.p);
+            let mixture_pdf = PDF::mixture(&light_pdf, &pdf);
+            let dir = mixture_pdf.generate();
+            let scattered = Ray::new(rec.p, dir, r.time());
+            let pdf = mixture_pdf.value(scattered.direction());
+            let scattering_pdf = rec.material.scattering_pdf(r, rec, &scattered);
+            Some((scattered, attenuation * (scattering_pdf / pdf)))
+        }
+        ScatterRecord::Specular {
+            specular_ray,
+            attenuation,
+        } => Some((specular_ray, attenuation)),
+    }
+}
+
+// Sample for a camera ray that escaped the scene.
+pub fn background_sample(background: &Color) -> (Color, AovSample, Option<i32>) {
+    let aov = AovSample {
+        emission: *background,
+        ..AovSample::default()
+    };
+    (aov.emission, aov, None)
+}
+
+// AOVs of the surface a camera ray hit, before any lighting but emission.
+pub fn surface_aovs(r: &Ray, rec: &HitRecord) -> AovSample {
+    AovSample {
+        albedo: rec.material.albedo(rec),
+        normal: rec.material.shading_normal(rec),
+        depth: rec.t * r.direction().length(),
+        position: rec.p,
+        uv: (rec.u, rec.v),
+        object_id: Some(rec.object_id),
+        material_id: Some(rec.material.id()),
+        emission: rec.material.emitted(r, rec),
+        ..AovSample::default()
+    }
+}
+
+// AOVs for a camera ray whose radiance came from elsewhere, such as BDPT.
+// The reflected light isn't split into direct and indirect, so all of it
+// goes to the indirect pass.
+pub fn first_hit_aovs(
+    r: &Ray,
+    world: &dyn Hittable,
+    background: &Color,
+    radiance: &Color,
+) -> AovSample {
+    let mut aov = match world.hit(r, 0.001, INFINITY) {
+        Some(rec) => surface_aovs(r, &rec),
+        None => AovSample {
+            emission: *background,
+            ..AovSample::default()
+        },
+    };
+    aov.indirect = radiance - &aov.emission;
+    aov
+}
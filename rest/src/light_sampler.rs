@@ -0,0 +1,137 @@
+use crate::hittable::*;
+use crate::onb::ONB;
+use crate::pdf::random_cosine_direction;
+use crate::ray::Ray;
+use crate::rtweekend::INFINITY;
+use crate::sampler;
+use crate::vec3::*;
+use rand::Rng;
+use std::f64::consts::PI;
+
+pub fn is_black(c: &Color) -> bool {
+    c.x() == 0.0 && c.y() == 0.0 && c.z() == 0.0
+}
+
+// Point on a light, picked by `LightSampler::sample`.
+pub struct LightPoint {
+    pub p: Point3,
+    pub n: Vec3,
+    // Area density of having picked `p`.
+    pub pdf: f64,
+    // Whether the front and back of the surface emit.
+    pub emits: [bool; 2],
+}
+
+impl LightPoint {
+    // Solid-angle density of `LightSampler::sample_ray` leaving toward `w`.
+    pub fn pdf_dir(&self, w: &Vec3) -> f64 {
+        let cosine = dot(&unit_vector(w), &self.n);
+        let sides = self.emits.iter().filter(|&&e| e).count();
+        if !self.emits[if cosine >= 0.0 { 0 } else { 1 }] {
+            return 0.0;
+        }
+        cosine.abs() / PI / sides as f64
+    }
+}
+
+// Starts paths on the lights, for integrators that trace light toward the
+// camera. Points are picked with the lights' `random` and `pdf_value` as
+// seen from the middle of the scene, and rays leave them cosine-weighted
+// through whichever sides emit. Emission itself is looked up in `world`, so
+// the lights list may hold unflipped or non-emitting shapes.
+pub struct LightSampler<'a> {
+    world: &'a dyn Hittable,
+    lights: &'a dyn Hittable,
+    origin: Point3,
+}
+
+impl<'a> LightSampler<'a> {
+    pub fn new(world: &'a dyn Hittable, lights: &'a dyn Hittable) -> Self {
+        let origin = world
+            .bounding_box(0.0, 1.0)
+            .map_or(Point3::default(), |b| 0.5 * (b.min + b.max));
+        LightSampler {
+            world,
+            lights,
+            origin,
+        }
+    }
+
+    // Emitted radiance leaving `p` toward `dir`, found by looking back at
+    // `p` from just off the surface so the world's own orientation and
+    // material apply.
+    pub fn le(&self, p: &Point3, dir: &Vec3, time: f64) -> Color {
+        let eps = 1e-4 * (1.0 + p.x().abs().max(p.y().abs()).max(p.z().abs()));
+        let dir = unit_vector(dir);
+        let ray = Ray::new(p + &(2.0 * eps * dir), -dir, time);
+        match self.world.hit(&ray, eps, 3.0 * eps) {
+            Some(rec) => rec.material.emitted(&ray, &rec),
+            None => Color::default(),
+        }
+    }
+
+    pub fn emitting_sides(&self, p: &Point3, n: &Vec3, time: f64) -> [bool; 2] {
+        [
+            !is_black(&self.le(p, n, time)),
+            !is_black(&self.le(p, &-n, time)),
+        ]
+    }
+
+    // Area density of `sample` picking `p`.
+    pub fn pdf(&self, p: &Point3, time: f64) -> f64 {
+        let d = p - &self.origin;
+        let dist = d.length();
+        if dist == 0.0 {
+            return 0.0;
+        }
+        let dir = d / dist;
+        let ray = Ray::new(self.origin, dir, time);
+        match self.lights.hit(&ray, 0.001, INFINITY) {
+            // Only the first crossing along each direction is ever sampled.
+            Some(rec) if (rec.p - *p).length() < 1e-4 * (1.0 + dist) => {
+                self.lights.pdf_value(&self.origin, &dir) * dot(&rec.normal, &dir).abs()
+                    / (dist * dist)
+            }
+            _ => 0.0,
+        }
+    }
+
+    pub fn sample(&self, time: f64) -> Option<LightPoint> {
+        let dir = unit_vector(&self.lights.random(&self.origin));
+        let ray = Ray::new(self.origin, dir, time);
+        let rec = self.lights.hit(&ray, 0.001, INFINITY)?;
+        let pdf = self.pdf(&rec.p, time);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightPoint {
+            p: rec.p,
+            n: rec.normal,
+            pdf,
+            emits: self.emitting_sides(&rec.p, &rec.normal, time),
+        })
+    }
+
+    // Ray leaving `point`, with its throughput Le cos / (pdf * pdf_dir) and
+    // solid-angle density.
+    pub fn sample_ray(&self, point: &LightPoint, time: f64) -> Option<(Ray, Color, f64)> {
+        let sides = point.emits.iter().filter(|&&e| e).count();
+        if sides == 0 {
+            return None;
+        }
+        let front = if sides == 2 {
+            sampler::rng().gen::<bool>()
+        } else {
+            point.emits[0]
+        };
+        let n = if front { point.n } else { -point.n };
+        let dir = ONB::build_from_w(&n).local(&random_cosine_direction());
+        let pdf_dir = point.pdf_dir(&dir);
+        if pdf_dir <= 0.0 {
+            return None;
+        }
+        let cosine = dot(&unit_vector(&dir), &n);
+        let beta = self.le(&point.p, &dir, time) * (cosine / (point.pdf * pdf_dir));
+        Some((Ray::new(point.p, dir, time), beta, pdf_dir))
+    }
+}
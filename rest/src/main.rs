@@ -1,39 +1,91 @@
 mod aabb;
 mod aarect;
+mod adaptive;
+mod ambient_occlusion;
+mod animated_transform;
+mod aov;
+mod bdpt;
 mod bvh;
 mod camera;
+mod camera_path;
+mod capsule;
 mod color;
+mod cone;
+mod csg;
 mod cube;
+mod curve;
+mod cutout;
+mod cylinder;
+mod debug_view;
+mod denoise;
+mod diagnostics;
+mod direct_lighting;
+mod disk;
+mod film;
+mod hair;
 mod hittable;
 mod hittable_list;
+mod infinite_plane;
+mod integrator;
+mod light_sampler;
 mod material;
+mod mesh;
+mod mlt;
 mod moving_sphere;
 mod onb;
 mod pdf;
 mod perlin;
+mod poly;
+mod quad;
+mod quaternion;
 mod ray;
+mod realistic_camera;
 mod rotate;
 mod rtweekend;
+mod sampler;
+mod sdf;
+mod settings;
 mod sphere;
+mod sppm;
 mod texture;
+mod texture_graph;
+mod torus;
 mod translate;
+mod triangle;
 mod vec3;
+mod worley;
 
 use aarect::*;
-use camera::Camera;
+use adaptive::*;
+use ambient_occlusion::AmbientOcclusion;
+use animated_transform::*;
+use aov::*;
+use bdpt::Bdpt;
+use camera::*;
+use camera_path::*;
 use color::*;
 use cube::*;
+use debug_view::DebugShader;
+use denoise::Denoiser;
+use diagnostics::*;
+use direct_lighting::DirectLighting;
+use film::*;
 use hittable::*;
 use hittable_list::HittableList;
+use integrator::*;
+use light_sampler::LightSampler;
 use material::*;
+use mlt::Mlt;
 use pdf::*;
+use quaternion::Quaternion;
 use rand::Rng;
-use ray::Ray;
-use rayon::iter::{IndexedParallelIterator, IntoParallelIterator};
+use rayon::iter::IntoParallelIterator;
 use rayon::prelude::*;
+use realistic_camera::{load_prescription, LensMount, RealisticCamera};
 use rotate::*;
-use rtweekend::*;
+use settings::{IntegratorKind, RenderSettings};
 use sphere::Sphere;
+use sppm::Sppm;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::sync::Mutex;
@@ -41,7 +93,8 @@ use texture::*;
 use translate::*;
 use vec3::*;
 
-fn cornell_box() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+// With `animated`, the aluminum box spins a quarter turn per second of scene time.
+fn cornell_box(animated: bool) -> (Box<dyn Hittable>, HittableList) {
     let red = Lambertian::new(SolidColor::new(0.65, 0.05, 0.05));
     let white = Lambertian::new(SolidColor::new(0.73, 0.73, 0.73));
     let green = Lambertian::new(SolidColor::new(0.12, 0.45, 0.15));
@@ -82,18 +135,31 @@ fn cornell_box() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
         555.0,
         white.clone(),
     ));
-    world.push(Translate::new(
-        Rotate::new(
-            Axis::Y,
-            Cube::new(
-                Vec3::new(0.0, 0.0, 0.0),
-                Vec3::new(165.0, 330.0, 165.0),
-                aluminum,
-            ),
-            15.0,
-        ),
-        Vec3::new(265.0, 0.0, 295.0),
-    ));
+    let tall_box = Cube::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(165.0, 330.0, 165.0),
+        aluminum,
+    );
+    if animated {
+        let y_axis = Vec3::new(0.0, 1.0, 0.0);
+        let key = |time: f64, angle: f64| {
+            Keyframe::new(
+                time,
+                Vec3::new(265.0, 0.0, 295.0),
+                Quaternion::from_axis_angle(&y_axis, angle),
+                Vec3::new(1.0, 1.0, 1.0),
+            )
+        };
+        let spin = (0..=4)
+            .map(|i| key(i as f64, 15.0 + 90.0 * i as f64))
+            .collect();
+        world.push(AnimatedTransform::new(tall_box, KeyframeTrack::new(spin)));
+    } else {
+        world.push(Translate::new(
+            Rotate::new(Axis::Y, tall_box, 15.0),
+            Vec3::new(265.0, 0.0, 295.0),
+        ));
+    }
     // world.push(Translate::new(
     //     Rotate::new(
     //         Axis::Y,
@@ -112,113 +178,400 @@ fn cornell_box() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
     lights.push(light_shape);
     lights.push(glass_sphere);
 
-    (Box::new(world), Box::new(lights))
+    (Box::new(world), lights)
 }
 
-fn ray_color(
-    r: &Ray,
-    background: &Color,
-    world: &Box<dyn Hittable>,
-    lights: &Box<dyn Hittable>,
-    depth: i32,
-) -> Color {
-    // If we've exceeded the ray bounce limit, no more light is gathered.
-    if depth <= 0 {
-        return Color::new(0., 0., 0.);
-    }
-
-    if let Some(rec) = world.hit(r, 0.001, INFINITY) {
-        let emitted = rec.material.emitted(&r, &rec);
-
-        if let Some(srec) = rec.material.scatter(r, &rec) {
-            match srec {
-                ScatterRecord::Scatter { pdf, attenuation } => {
-                    let light_pdf = PDF::hittable(lights, &rec.p);
-                    let mixture_pdf = PDF::mixture(&light_pdf, &pdf);
-                    let dir = mixture_pdf.generate();
-                    let scattered = Ray::new(rec.p, dir, r.time());
-                    let pdf = mixture_pdf.value(&scattered.direction());
-                    let scattering_pdf = rec.material.scattering_pdf(&r, &rec, &scattered);
-                    return emitted
-                        + &ray_color(&scattered, background, world, lights, depth - 1)
-                            * &attenuation
-                            * (scattering_pdf / pdf);
-                }
-                ScatterRecord::Specular {
-                    specular_ray,
-                    attenuation,
-                } => {
-                    return &attenuation
-                        * &ray_color(&specular_ray, background, world, lights, depth - 1)
-                }
-            }
-        }
-        return emitted;
-    }
-    background.clone()
+// Slow dolly toward the back wall over four seconds.
+fn cornell_box_camera_path() -> CameraPath {
+    let lookat = Point3::new(278., 278., 0.);
+    let start = Point3::new(278., 278., -800.);
+    let end = Point3::new(278., 278., -500.);
+    CameraPath::new(
+        vec![
+            CameraKey::new(0.0, start, lookat, 40.0, 0.0, (lookat - start).length()),
+            CameraKey::new(4.0, end, lookat, 40.0, 0.0, (lookat - end).length()),
+        ],
+        Vec3::new(0.0, 1.0, 0.0),
+    )
 }
 
-fn main() {
-    const MAX_DEPTH: i32 = 50;
+// Gamma-corrected 8-bit pixels, rows from top to bottom.
+type Image = Vec<Vec<(i32, i32, i32)>>;
 
-    //world
-    let (world, lights) = cornell_box();
-    let aperture: f64 = 0.0;
+// Everything a render produces.
+struct Frame {
+    // Size of the crop window that was rendered.
+    width: i32,
+    height: i32,
+    image: Image,
+    aovs: Option<AovBuffer>,
+    // Samples each pixel took, in the same layout as `image`.
+    sample_counts: Vec<Vec<i32>>,
+    diagnostics: Diagnostics,
+}
 
-    let aspect_ratio = 1.0;
-    let image_width = 600;
-    let samples_per_pixel = 1000;
-    let background = Color::default();
-    let lookfrom = Point3::new(278., 278., -800.);
-    let lookat = Point3::new(278., 278., 0.);
-    let vfov = 40.0;
-    let image_height: i32 = (image_width as f64 / aspect_ratio) as i32;
-
-    let vup = Vec3(0.0, 1.0, 0.0);
-    let dist_to_focus: f64 = 10.0;
-
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        vfov,
-        aspect_ratio,
-        aperture,
-        dist_to_focus,
-        0.0,
-        1.0,
+// Integrator shading camera rays one at a time for `settings`. Debug views
+// replace the lighting whatever the integrator, and integrators that aren't
+// per ray get the path tracer, which MLT drives.
+fn camera_integrator<'a>(
+    world: &'a dyn Hittable,
+    lights: &'a dyn Hittable,
+    background: &Color,
+    settings: &RenderSettings,
+) -> Box<dyn Integrator + 'a> {
+    let diagonal = world
+        .bounding_box(0.0, 1.0)
+        .map_or(1.0, |b| (b.max - b.min).length());
+    if let Some(view) = settings.debug_view {
+        return Box::new(DebugShader {
+            view,
+            scale: settings.debug_scale.unwrap_or(view.default_scale(diagonal)),
+            world,
+            lights,
+            background: *background,
+            max_depth: settings.max_depth,
+        });
+    }
+    match settings.integrator {
+        IntegratorKind::Ao => Box::new(AmbientOcclusion {
+            world,
+            background: *background,
+            distance: settings.ao_distance.unwrap_or(diagonal / 10.0),
+            samples: settings.ao_samples,
+        }),
+        IntegratorKind::Direct => Box::new(DirectLighting {
+            world,
+            lights,
+            background: *background,
+        }),
+        _ => Box::new(PathTracer {
+            world,
+            lights,
+            background: *background,
+            max_depth: settings.max_depth,
+            clamp: settings.clamp_indirect,
+        }),
+    }
+}
+
+fn render(
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+    cam: &dyn CameraModel,
+    background: &Color,
+    settings: &RenderSettings,
+    image_height: i32,
+) -> Frame {
+    let image_width = settings.image_width;
+    let samples_per_pixel = settings.samples_per_pixel;
+    let max_depth = settings.max_depth;
+    // The denoiser is guided by the AOVs, so it needs them too.
+    let collect_aovs = settings.aovs || settings.denoise;
+    let mut film = Film::new(
+        image_width as usize,
+        image_height as usize,
+        settings.filter,
+        settings.crop,
     );
+    let integrator = camera_integrator(world, lights, background, settings);
+    let debug = settings.debug_view.is_some();
+    if !debug && settings.integrator == IntegratorKind::Mlt {
+        return render_mlt(
+            integrator.as_ref(),
+            world,
+            cam,
+            background,
+            settings,
+            film,
+            image_height,
+        );
+    }
+    if !debug && settings.integrator == IntegratorKind::Sppm {
+        return render_sppm(
+            world,
+            lights,
+            cam,
+            background,
+            settings,
+            &film,
+            image_height,
+        );
+    }
+    let bdpt = match settings.integrator {
+        _ if debug => None,
+        IntegratorKind::Path
+        | IntegratorKind::Sppm
+        | IntegratorKind::Mlt
+        | IntegratorKind::Ao
+        | IntegratorKind::Direct => None,
+        IntegratorKind::Bdpt => Some(Bdpt {
+            world,
+            lights: LightSampler::new(world, lights),
+            camera: cam,
+            background: *background,
+            max_depth,
+            image_width: image_width as usize,
+            image_height: image_height as usize,
+            crop: (film.x0, film.x1, film.y0, film.y1),
+        }),
+    };
+    let bdpt_ref = bdpt.as_ref();
     let counter = Mutex::new(0);
-    let mut result: Vec<Vec<(i32, i32, i32)>> = Vec::with_capacity(image_height as usize);
-    (0..image_height as i32)
+    let film_ref = &film;
+    // BDPT splats land anywhere on the film, so each rayon job gets one
+    // buffer for all its rows, merged once the job is done.
+    let new_splats = || match bdpt_ref {
+        Some(_) => film_ref.splat_buffer(),
+        None => Vec::new(),
+    };
+    let rows: Vec<_> = (film.y0..film.y1)
         .into_par_iter()
-        .rev()
-        .map(move |j| {
-            let mut color: Vec<(i32, i32, i32)> = Vec::with_capacity(image_width as usize);
-            let mut rng = rand::thread_rng();
-            for i in 0..image_width {
-                let mut pixel_color = Color::new(0., 0., 0.);
-                for _ in 0..samples_per_pixel {
-                    let u = (i as f64 + rng.gen::<f64>()) / (image_width as f64 - 1.0);
-                    let v = (j as f64 + rng.gen::<f64>()) / (image_height as f64 - 1.0);
-                    let r = cam.get_ray(u, v);
-                    pixel_color += ray_color(&r, &background, &world, &lights, MAX_DEPTH);
+        .fold(
+            || (Vec::new(), new_splats()),
+            move |(mut rows, mut splats), row| {
+                // Camera rows count up from the bottom of the image.
+                let j = image_height as usize - 1 - row;
+                let mut tile = film_ref.tile(row);
+                let mut aovs: Vec<AovPixel> = Vec::new();
+                let mut counts: Vec<i32> = Vec::with_capacity(film_ref.crop_width());
+                let mut invalid = Diagnostics::default();
+                let mut rng = rand::thread_rng();
+                for i in film_ref.x0..film_ref.x1 {
+                    let mut aov_pixel = AovPixel::default();
+                    let mut stats = PixelStats::default();
+                    // With adaptive sampling `samples_per_pixel` is only the cap.
+                    while stats.samples() < samples_per_pixel {
+                        let x = i as f64 + rng.gen::<f64>();
+                        let y = j as f64 + rng.gen::<f64>();
+                        let u = x / (image_width as f64 - 1.0);
+                        let v = y / (image_height as f64 - 1.0);
+                        let (r, weight) = cam.get_weighted_ray(u, v);
+                        let (radiance, aov, invalid_at) = if weight <= 0.0 {
+                            (Color::default(), AovSample::default(), None)
+                        } else if let Some(bdpt) = bdpt_ref {
+                            let radiance = bdpt.sample(&r, &mut |x, y, c| {
+                                film_ref.add_splat(&mut splats, x, y, &c)
+                            });
+                            let aov = if collect_aovs {
+                                first_hit_aovs(&r, world, background, &radiance)
+                            } else {
+                                AovSample::default()
+                            };
+                            (radiance, aov, None)
+                        } else {
+                            integrator.ray_color(&r)
+                        };
+                        let sample = weight * radiance;
+                        if let Some(problem) = Problem::of(&sample) {
+                            invalid.add(InvalidSample {
+                                x: i - film_ref.x0,
+                                y: row - film_ref.y0,
+                                bounce: invalid_at,
+                                problem,
+                            });
+                        }
+                        film_ref.add_sample(&mut tile, x, image_height as f64 - y, &sample);
+                        stats.add(&sample);
+                        if collect_aovs {
+                            aov_pixel.add(&aov, weight);
+                        }
+                        if settings
+                            .adaptive_threshold
+                            .is_some_and(|t| stats.converged(t, settings.min_samples))
+                        {
+                            break;
+                        }
+                    }
+                    counts.push(stats.samples());
+                    if collect_aovs {
+                        aovs.push(aov_pixel);
+                    }
                 }
-                color.push(get_color(pixel_color, samples_per_pixel));
-            }
-            {
-                let mut num = counter.lock().unwrap();
-                *num += 1;
-                eprint!("\rScanlines remaining: {} ", image_height - *num);
+                {
+                    let mut num = counter.lock().unwrap();
+                    *num += 1;
+                    eprint!("\rScanlines remaining: {} ", film_ref.crop_height() - *num);
+                }
+                rows.push((tile, aovs, counts, invalid));
+                (rows, splats)
+            },
+        )
+        .flat_map_iter(|(rows, splats)| {
+            if bdpt_ref.is_some() {
+                film_ref.merge_splats(&splats);
             }
-            color
+            rows
         })
-        .collect_into_vec(&mut result);
+        .collect();
+    let mut aovs = Vec::new();
+    let mut sample_counts = Vec::with_capacity(rows.len());
+    let mut diagnostics = Diagnostics::default();
+    for (tile, row_aovs, counts, invalid) in rows {
+        film.merge(tile);
+        aovs.extend(row_aovs);
+        sample_counts.push(counts);
+        diagnostics.merge(invalid);
+    }
+    if bdpt.is_some() {
+        let total: i32 = sample_counts.iter().flatten().sum();
+        let pixels = film.crop_width() * film.crop_height();
+        film.set_splat_scale(pixels as f64 / total.max(1) as f64);
+    }
+    let aovs = if collect_aovs {
+        Some(AovBuffer::new(film.crop_width(), film.crop_height(), aovs))
+    } else {
+        None
+    };
+    Frame {
+        width: film.crop_width() as i32,
+        height: film.crop_height() as i32,
+        image: film.image(),
+        aovs,
+        sample_counts,
+        diagnostics,
+    }
+}
+
+// SPPM works per pixel rather than per sample, so the film only supplies the
+// crop window.
+fn render_sppm(
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+    cam: &dyn CameraModel,
+    background: &Color,
+    settings: &RenderSettings,
+    film: &Film,
+    image_height: i32,
+) -> Frame {
+    let image_width = settings.image_width as usize;
+    let image_height = image_height as usize;
+    let width = film.crop_width();
+    let height = film.crop_height();
+    let diagonal = world
+        .bounding_box(0.0, 1.0)
+        .map_or(1.0, |b| (b.max - b.min).length());
+    let sppm = Sppm {
+        world,
+        lights,
+        sampler: LightSampler::new(world, lights),
+        camera: cam,
+        background: *background,
+        max_depth: settings.max_depth,
+        photons_per_iteration: settings.photons.unwrap_or(width * height),
+        initial_radius: settings.photon_radius.unwrap_or(diagonal / 200.0),
+        final_gather: settings.final_gather,
+        image_width,
+        image_height,
+        crop: (film.x0, film.x1, film.y0, film.y1),
+    };
+    let radiance = sppm.render(settings.samples_per_pixel);
+    radiance_frame(
+        radiance,
+        world,
+        cam,
+        background,
+        settings,
+        film,
+        image_height as i32,
+    )
+}
 
+// Metropolis sampling only splats, so the film only collects those.
+fn render_mlt(
+    integrator: &dyn Integrator,
+    world: &dyn Hittable,
+    cam: &dyn CameraModel,
+    background: &Color,
+    settings: &RenderSettings,
+    mut film: Film,
+    image_height: i32,
+) -> Frame {
+    let mlt = Mlt {
+        camera: cam,
+        integrator,
+        bootstrap_samples: settings.bootstrap_samples,
+        chains: settings.chains,
+        image_width: settings.image_width as usize,
+        image_height: image_height as usize,
+        crop: (film.x0, film.x1, film.y0, film.y1),
+    };
+    let scale = mlt.render(&film, settings.samples_per_pixel);
+    film.set_splat_scale(scale);
+    radiance_frame(
+        film.radiance(),
+        world,
+        cam,
+        background,
+        settings,
+        &film,
+        image_height,
+    )
+}
+
+// Frame for integrators that only produce final pixel values for the crop
+// window, with AOVs taken through each pixel's centre.
+fn radiance_frame(
+    radiance: Vec<Color>,
+    world: &dyn Hittable,
+    cam: &dyn CameraModel,
+    background: &Color,
+    settings: &RenderSettings,
+    film: &Film,
+    image_height: i32,
+) -> Frame {
+    let image_width = settings.image_width as usize;
+    let image_height = image_height as usize;
+    let width = film.crop_width();
+    let height = film.crop_height();
+    let mut diagnostics = Diagnostics::default();
+    for (k, c) in radiance.iter().enumerate() {
+        if let Some(problem) = Problem::of(c) {
+            diagnostics.add(InvalidSample {
+                x: k % width,
+                y: k / width,
+                bounce: None,
+                problem,
+            });
+        }
+    }
+    let aovs = if settings.aovs || settings.denoise {
+        let pixels = radiance
+            .iter()
+            .enumerate()
+            .map(|(k, c)| {
+                let u = (film.x0 + k % width) as f64 + 0.5;
+                let v = (image_height - 1 - (film.y0 + k / width)) as f64 + 0.5;
+                let r = cam.get_ray(
+                    u / (image_width as f64 - 1.0),
+                    v / (image_height as f64 - 1.0),
+                );
+                let mut pixel = AovPixel::default();
+                pixel.add(&first_hit_aovs(&r, world, background, c), 1.0);
+                pixel
+            })
+            .collect();
+        Some(AovBuffer::new(width, height, pixels))
+    } else {
+        None
+    };
+    Frame {
+        width: width as i32,
+        height: height as i32,
+        image: radiance
+            .chunks(width)
+            .map(|row| row.iter().map(|c| get_color(*c, 1)).collect())
+            .collect(),
+        aovs,
+        sample_counts: vec![vec![settings.samples_per_pixel; width]; height],
+        diagnostics,
+    }
+}
+
+fn write_image(path: &str, image_width: i32, image_height: i32, result: &[Vec<(i32, i32, i32)>]) {
     eprintln!("\nFile output start.");
     let mut file = BufWriter::with_capacity(
         (image_width * image_height * 11 + 17) as usize,
-        File::create("image.ppm").expect("Unable to create file"),
+        File::create(path).expect("Unable to create file"),
     );
     let header = format!("P3\n{} {}\n255\n", image_width, image_height);
     file.write_all(header.as_bytes())
@@ -231,5 +584,190 @@ fn main() {
         });
     });
     file.flush().unwrap();
+}
+
+// Writes the image and reports invalid samples, then whichever of the debug
+// image, sample heatmap, AOVs and denoised image the settings ask for.
+fn write_outputs(output: &str, settings: &RenderSettings, frame: &Frame) {
+    write_image(output, frame.width, frame.height, &frame.image);
+    frame.diagnostics.report();
+    if settings.debug_invalid {
+        write_image(
+            &aov_path(output, "invalid", "ppm"),
+            frame.width,
+            frame.height,
+            &frame.diagnostics.debug_image(&frame.image),
+        );
+    }
+    if settings.heatmap {
+        let heatmap: Image = frame
+            .sample_counts
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&n| heatmap_color(n, settings.samples_per_pixel))
+                    .collect()
+            })
+            .collect();
+        write_image(
+            &aov_path(output, "spp", "ppm"),
+            frame.width,
+            frame.height,
+            &heatmap,
+        );
+    }
+    let aovs = match &frame.aovs {
+        Some(aovs) => aovs,
+        None => return,
+    };
+    if settings.aovs {
+        aovs.write(output);
+    }
+    if settings.denoise {
+        let denoised = Denoiser::default().denoise(aovs.width(), aovs.height(), &aovs.resolve());
+        let denoised: Image = denoised
+            .chunks(aovs.width())
+            .map(|row| row.iter().map(|c| get_color(*c, 1)).collect())
+            .collect();
+        write_image(
+            &aov_path(output, "denoised", "ppm"),
+            frame.width,
+            frame.height,
+            &denoised,
+        );
+    }
+}
+
+// Checks that every light's pdf integrates to ~1 as seen from `origin` and that
+// its own samples land where the pdf is non-zero. Returns false on failure.
+fn validate_lights(lights: &HittableList, origin: &Point3) -> bool {
+    const SAMPLES: usize = 1_000_000;
+    let mut ok = true;
+    for (i, light) in lights.objects.iter().enumerate() {
+        let integral = integrate_pdf_value(light.as_ref(), origin, SAMPLES);
+        let missed = (0..SAMPLES / 100)
+            .filter(|_| light.pdf_value(origin, &light.random(origin)) <= 0.0)
+            .count();
+        // Only shapes that say they can miss by rounding get any slack.
+        let allowed = light.sampling_tolerance() * (SAMPLES / 100) as f64;
+        // Light sampling has no notion of time, so lights can't move.
+        let moves = match (light.bounding_box(0.0, 0.0), light.bounding_box(0.0, 1.0)) {
+            (Some(a), Some(b)) => (a.min - b.min).length() > 0.0 || (a.max - b.max).length() > 0.0,
+            _ => false,
+        };
+        let valid = (integral - 1.0).abs() < 0.05 && missed as f64 <= allowed && !moves;
+        eprintln!(
+            "light {}: pdf integral {:.4}, {} of {} samples with zero pdf{}{}",
+            i,
+            integral,
+            missed,
+            SAMPLES / 100,
+            if moves {
+                ", moves during the shutter"
+            } else {
+                ""
+            },
+            if valid { "" } else { "  <-- FAILED" }
+        );
+        ok &= valid;
+    }
+    ok
+}
+
+fn main() {
+    let settings = RenderSettings::from_args();
+
+    if settings.validate_lights {
+        let (_, lights) = cornell_box(false);
+        let ok = validate_lights(&lights, &Point3::new(278.0, 278.0, 278.0));
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    let aspect_ratio = 1.0;
+    let background = Color::default();
+    let image_height: i32 = (settings.image_width as f64 / aspect_ratio) as i32;
+
+    match settings.frames {
+        None => {
+            //world
+            let (world, lights) = cornell_box(false);
+            let lights: Box<dyn Hittable> = Box::new(lights);
+            let aperture: f64 = 0.0;
+            let lookfrom = Point3::new(278., 278., -800.);
+            let lookat = Point3::new(278., 278., 0.);
+            let vfov = 40.0;
+            let vup = Vec3(0.0, 1.0, 0.0);
+            let dist_to_focus = Camera::focus_distance(&lookfrom, &lookat, &lookat);
+
+            let cam: Box<dyn CameraModel> = match &settings.lens {
+                // A 50mm lens on a 50mm film diagonal frames the box about as the
+                // 40 degree pinhole does, with world units as millimetres.
+                Some(path) => Box::new(
+                    RealisticCamera::new(
+                        lookfrom,
+                        lookat,
+                        vup,
+                        LensMount {
+                            elements: load_prescription(path),
+                            world_per_mm: 1.0,
+                            film_diagonal: 50.0,
+                            aperture_diameter: settings.lens_aperture.unwrap_or(f64::INFINITY),
+                            focus_dist: dist_to_focus,
+                        },
+                        aspect_ratio,
+                        0.0,
+                        1.0,
+                    )
+                    .with_shutter(settings.shutter()),
+                ),
+                None => Box::new(
+                    Camera::new(
+                        lookfrom,
+                        lookat,
+                        vup,
+                        vfov,
+                        aspect_ratio,
+                        aperture,
+                        dist_to_focus,
+                        0.0,
+                        1.0,
+                    )
+                    .with_shutter(settings.shutter()),
+                ),
+            };
+            let frame = render(
+                world.as_ref(),
+                lights.as_ref(),
+                cam.as_ref(),
+                &background,
+                &settings,
+                image_height,
+            );
+            write_outputs(&settings.output, &settings, &frame);
+        }
+        Some((start, end)) => {
+            let (world, lights) = cornell_box(true);
+            let lights: Box<dyn Hittable> = Box::new(lights);
+            let path = cornell_box_camera_path();
+            for frame in start..=end {
+                let time0 = frame as f64 / settings.fps;
+                let time1 = time0 + settings.shutter / settings.fps;
+                let cam = path
+                    .camera(aspect_ratio, time0, time1)
+                    .with_shutter(settings.shutter());
+                eprintln!("Frame {} ({}..={})", frame, start, end);
+                let output = settings.frame_output(frame);
+                let rendered = render(
+                    world.as_ref(),
+                    lights.as_ref(),
+                    &cam,
+                    &background,
+                    &settings,
+                    image_height,
+                );
+                write_outputs(&output, &settings, &rendered);
+            }
+        }
+    }
     eprintln!("Done.");
 }
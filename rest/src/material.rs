@@ -1,9 +1,12 @@
+use crate::color::luminance;
 use crate::hittable::HitRecord;
 use crate::pdf::*;
 use crate::ray::Ray;
 use crate::rtweekend::random_double;
 use crate::texture::*;
 use crate::vec3::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 pub enum ScatterRecord<'a> {
     Specular {
@@ -28,50 +31,112 @@ pub trait Material: Sync + Send {
     fn emitted(&self, _ray: &Ray, _hit: &HitRecord) -> Vec3 {
         Vec3::default()
     }
+
+    // Surface colour at the hit, independent of lighting, for the albedo AOV.
+    fn albedo(&self, _hit: &HitRecord) -> Color {
+        Color::default()
+    }
+
+    // Normal actually shaded with, after any normal or bump mapping.
+    fn shading_normal(&self, hit: &HitRecord) -> Vec3 {
+        hit.shading_normal
+    }
+
+    // Number from `next_material_id` for the material ID AOV and debug
+    // view, shared by clones of the material.
+    fn id(&self) -> usize;
+}
+
+// Hands out material IDs in the order materials are made, so they come out
+// the same on every run.
+pub fn next_material_id() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+// Lets many primitives, such as the triangles of a mesh, share one material.
+impl<M: Material + ?Sized> Material for Arc<M> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        (**self).scatter(r_in, rec)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &HitRecord, scattered: &Ray) -> f64 {
+        (**self).scattering_pdf(ray, hit, scattered)
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &HitRecord) -> Vec3 {
+        (**self).emitted(ray, hit)
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Color {
+        (**self).albedo(hit)
+    }
+
+    fn shading_normal(&self, hit: &HitRecord) -> Vec3 {
+        (**self).shading_normal(hit)
+    }
+
+    fn id(&self) -> usize {
+        (**self).id()
+    }
 }
 
 #[derive(Clone)]
 pub struct Lambertian<T: Texture> {
     pub albedo: T,
+    id: usize,
 }
 
 impl<T: Texture> Lambertian<T> {
     pub fn new(albedo: T) -> Self {
-        Lambertian { albedo }
+        Lambertian {
+            albedo,
+            id: next_material_id(),
+        }
     }
 }
 
 impl Lambertian<SolidColor> {
     #[allow(dead_code)]
     pub fn from_color(c: Color) -> Self {
-        Lambertian {
-            albedo: SolidColor::from_color(c),
-        }
+        Lambertian::new(SolidColor::from_color(c))
     }
 }
 
 impl<T: Texture> Material for Lambertian<T> {
     fn scatter(&self, _ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
         Some(ScatterRecord::Scatter {
-            pdf: PDF::cosine(&hit.normal),
-            attenuation: self.albedo.value(hit.u, hit.v, &hit.p),
+            pdf: PDF::cosine(&hit.shading_normal),
+            attenuation: self
+                .albedo
+                .value_with_normal(hit.u, hit.v, &hit.p, &hit.shading_normal),
         })
     }
 
     fn scattering_pdf(&self, _ray: &Ray, hit: &HitRecord, scattered: &Ray) -> f64 {
-        let cosine = dot(&hit.normal, &unit_vector(&scattered.direction()));
+        let cosine = dot(&hit.shading_normal, &unit_vector(&scattered.direction()));
         if cosine < 0. {
             0.
         } else {
             cosine / std::f64::consts::PI
         }
     }
+
+    fn albedo(&self, hit: &HitRecord) -> Color {
+        self.albedo
+            .value_with_normal(hit.u, hit.v, &hit.p, &hit.shading_normal)
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
 }
 
 #[derive(Clone)]
 pub struct Metal {
     pub albedo: Vec3,
     pub fuzz: f64,
+    id: usize,
 }
 
 impl Metal {
@@ -80,17 +145,18 @@ impl Metal {
         Metal {
             albedo,
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
+            id: next_material_id(),
         }
     }
 }
 
 impl Material for Metal {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
-        let mut reflected = reflect(&unit_vector(&ray.direction()), &hit.normal);
+        let mut reflected = reflect(&unit_vector(&ray.direction()), &hit.shading_normal);
         if self.fuzz > 0.0 {
             reflected += self.fuzz * random_in_unit_sphere()
         };
-        if dot(&reflected, &hit.normal) > 0.0 {
+        if dot(&reflected, &hit.shading_normal) > 0.0 {
             Some(ScatterRecord::Specular {
                 specular_ray: Ray::new(hit.p, reflected, ray.time()),
                 attenuation: self.albedo,
@@ -99,16 +165,28 @@ impl Material for Metal {
             None
         }
     }
+
+    fn albedo(&self, _hit: &HitRecord) -> Color {
+        self.albedo
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
 }
 
 #[derive(Clone)]
 pub struct Dielectric {
     pub ref_idx: f64,
+    id: usize,
 }
 
 impl Dielectric {
     pub fn new(ref_idx: f64) -> Self {
-        Self { ref_idx }
+        Self {
+            ref_idx,
+            id: next_material_id(),
+        }
     }
 }
 
@@ -121,22 +199,30 @@ impl Material for Dielectric {
         };
 
         let unit_direction = unit_vector(r_in.direction());
-        let cos_theta = dot(&-&unit_direction, &rec.normal).min(1.0);
+        let cos_theta = dot(&-&unit_direction, &rec.shading_normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
 
         let direction =
             if cannot_refract || reflectance(cos_theta, refraction_ratio) > random_double() {
-                reflect(&unit_direction, &rec.normal)
+                reflect(&unit_direction, &rec.shading_normal)
             } else {
-                refract(&unit_direction, &rec.normal, refraction_ratio)
+                refract(&unit_direction, &rec.shading_normal, refraction_ratio)
             };
         return Some(ScatterRecord::Specular {
             specular_ray: Ray::new(rec.p.clone(), direction, r_in.time()),
             attenuation: Vec3::new(1.0, 1.0, 1.0),
         });
     }
+
+    fn albedo(&self, _hit: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
 }
 
 pub fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
@@ -148,22 +234,163 @@ pub fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
 #[derive(Clone)]
 pub struct DiffuseLight<T: Texture> {
     emit: T,
+    id: usize,
 }
 
 impl<T: Texture> DiffuseLight<T> {
     pub fn new(emit: T) -> Self {
-        DiffuseLight { emit }
+        DiffuseLight {
+            emit,
+            id: next_material_id(),
+        }
     }
 }
 
 impl<T: Texture> Material for DiffuseLight<T> {
     fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Vec3 {
         if rec.front_face {
-            self.emit.value(rec.u, rec.v, &rec.p)
+            self.emit
+                .value_with_normal(rec.u, rec.v, &rec.p, &rec.shading_normal)
         } else {
             Color::default()
         }
     }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+// Perturbs the shading normal with a tangent-space normal map (RGB in [0, 1]
+// encoding XYZ in [-1, 1], Z along the surface normal) before delegating.
+#[derive(Clone)]
+pub struct NormalMap<M: Material, T: Texture> {
+    material: M,
+    map: T,
+    strength: f64,
+    id: usize,
+}
+
+impl<M: Material, T: Texture> NormalMap<M, T> {
+    #[allow(dead_code)]
+    pub fn new(material: M, map: T, strength: f64) -> Self {
+        NormalMap {
+            material,
+            map,
+            strength,
+            id: next_material_id(),
+        }
+    }
+
+    fn perturb<'a>(&self, hit: &HitRecord<'a>) -> HitRecord<'a> {
+        let c = self.map.value(hit.u, hit.v, &hit.p);
+        let x = self.strength * (2.0 * c.x() - 1.0);
+        let y = self.strength * (2.0 * c.y() - 1.0);
+        let z = 2.0 * c.z() - 1.0;
+        let (t, b) = hit.tangent_frame();
+        let n = x * t + y * b + z * hit.shading_normal;
+        let mut rec = hit.clone();
+        if !n.near_zero() {
+            rec.shading_normal = unit_vector(&n);
+        }
+        rec
+    }
+}
+
+impl<M: Material, T: Texture> Material for NormalMap<M, T> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        self.material.scatter(r_in, &self.perturb(rec))
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &HitRecord, scattered: &Ray) -> f64 {
+        self.material
+            .scattering_pdf(ray, &self.perturb(hit), scattered)
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &HitRecord) -> Vec3 {
+        self.material.emitted(ray, &self.perturb(hit))
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Color {
+        self.material.albedo(&self.perturb(hit))
+    }
+
+    fn shading_normal(&self, hit: &HitRecord) -> Vec3 {
+        self.material.shading_normal(&self.perturb(hit))
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+// Perturbs the shading normal by the gradient of a height texture, taken by
+// finite differences along dpdu and dpdv. The height is the texture's luminance.
+#[derive(Clone)]
+pub struct BumpMap<M: Material, T: Texture> {
+    material: M,
+    height: T,
+    scale: f64,
+    id: usize,
+}
+
+impl<M: Material, T: Texture> BumpMap<M, T> {
+    #[allow(dead_code)]
+    pub fn new(material: M, height: T, scale: f64) -> Self {
+        BumpMap {
+            material,
+            height,
+            scale,
+            id: next_material_id(),
+        }
+    }
+
+    fn perturb<'a>(&self, hit: &HitRecord<'a>) -> HitRecord<'a> {
+        const DELTA: f64 = 0.0005;
+        let h = |u: f64, v: f64, p: &Vec3| self.scale * luminance(&self.height.value(u, v, p));
+        let h0 = h(hit.u, hit.v, &hit.p);
+        let hu = h(hit.u + DELTA, hit.v, &(hit.p + DELTA * hit.dpdu));
+        let hv = h(hit.u, hit.v + DELTA, &(hit.p + DELTA * hit.dpdv));
+        let n = hit.shading_normal;
+        let dpdu = hit.dpdu + ((hu - h0) / DELTA) * n;
+        let dpdv = hit.dpdv + ((hv - h0) / DELTA) * n;
+        let mut bumped = cross(&dpdu, &dpdv);
+        let mut rec = hit.clone();
+        if !bumped.near_zero() {
+            if dot(&bumped, &n) < 0.0 {
+                bumped = -bumped;
+            }
+            rec.shading_normal = unit_vector(&bumped);
+        }
+        rec
+    }
+}
+
+impl<M: Material, T: Texture> Material for BumpMap<M, T> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        self.material.scatter(r_in, &self.perturb(rec))
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &HitRecord, scattered: &Ray) -> f64 {
+        self.material
+            .scattering_pdf(ray, &self.perturb(hit), scattered)
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &HitRecord) -> Vec3 {
+        self.material.emitted(ray, &self.perturb(hit))
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Color {
+        self.material.albedo(&self.perturb(hit))
+    }
+
+    fn shading_normal(&self, hit: &HitRecord) -> Vec3 {
+        self.material.shading_normal(&self.perturb(hit))
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
 }
 
 // #[derive(Clone)]
@@ -2,6 +2,7 @@ use crate::aabb::AABB;
 use crate::hittable::*;
 use crate::material::Isotropic;
 use crate::ray::Ray;
+use crate::sampler;
 use crate::texture::Texture;
 use crate::vec3::Vec3;
 use rand::Rng;
@@ -25,7 +26,7 @@ impl<H: Hittable, T: Texture> ConstantMedium<H, T> {
 
 impl<H: Hittable, T: Texture> Hittable for ConstantMedium<H, T> {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut rng = rand::thread_rng();
+        let mut rng = sampler::rng();
         if let Some(mut hit1) = self.boundary.hit(&ray, -f64::MAX, f64::MAX) {
             if let Some(mut hit2) = self.boundary.hit(&ray, hit1.t + 0.0001, f64::MAX) {
                 if hit1.t < t_min {
@@ -0,0 +1,351 @@
+use crate::color::luminance;
+use crate::hittable::Hittable;
+use crate::material::Material;
+use crate::texture::Texture;
+use crate::triangle::Triangle;
+use crate::vec3::*;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
+
+// Polygon mesh used at scene load: subdivide and displace it, then turn it
+// into triangles for a `BVH`. Faces index into `positions`, and `uvs` holds
+// one texture coordinate per position.
+#[derive(Clone)]
+pub struct Mesh {
+    pub positions: Vec<Point3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<Vec<usize>>,
+}
+
+// Reads `v`, `vt` and `f` lines from Wavefront OBJ text. Each vertex keeps the
+// texture coordinate it is first referenced with.
+pub fn parse_obj(text: &str) -> Mesh {
+    let mut positions = Vec::new();
+    let mut texcoords = Vec::new();
+    let mut faces = Vec::new();
+    let mut uvs: Vec<Option<(f64, f64)>> = Vec::new();
+    let number = |s: &str| -> f64 { s.parse().expect("Invalid number in OBJ file") };
+    for line in text.lines() {
+        let mut cols = line.split_whitespace();
+        match cols.next() {
+            Some("v") => {
+                let c: Vec<f64> = cols.take(3).map(number).collect();
+                positions.push(Vec3::new(c[0], c[1], c[2]));
+                uvs.push(None);
+            }
+            Some("vt") => {
+                let c: Vec<f64> = cols.take(2).map(number).collect();
+                texcoords.push((c[0], c.get(1).copied().unwrap_or(0.0)));
+            }
+            Some("f") => {
+                // Indices are 1-based; negative ones count back from the end.
+                let resolve = |s: &str, len: usize| -> usize {
+                    let i: i64 = s.parse().expect("Invalid index in OBJ file");
+                    let index = if i < 0 { len as i64 + i } else { i - 1 };
+                    if i == 0 || index < 0 || index >= len as i64 {
+                        panic!["OBJ index {} out of range in: {}", s, line]
+                    }
+                    index as usize
+                };
+                let face: Vec<usize> = cols
+                    .map(|vertex| {
+                        let mut refs = vertex.split('/');
+                        let v = resolve(refs.next().unwrap(), positions.len());
+                        if let Some(vt) = refs.next().filter(|vt| !vt.is_empty()) {
+                            let vt = resolve(vt, texcoords.len());
+                            uvs[v].get_or_insert(texcoords[vt]);
+                        }
+                        v
+                    })
+                    .collect();
+                if face.len() < 3 {
+                    panic!["OBJ face needs at least 3 vertices: {}", line]
+                }
+                faces.push(face);
+            }
+            _ => {}
+        }
+    }
+    Mesh {
+        positions,
+        uvs: uvs.into_iter().map(|uv| uv.unwrap_or((0.0, 0.0))).collect(),
+        faces,
+    }
+}
+
+#[allow(dead_code)]
+pub fn load_obj(path: &str) -> Mesh {
+    parse_obj(&fs::read_to_string(path).expect("Unable to read OBJ file"))
+}
+
+impl Mesh {
+    #[allow(dead_code)]
+    pub fn new(positions: Vec<Point3>, uvs: Vec<(f64, f64)>, faces: Vec<Vec<usize>>) -> Self {
+        if uvs.len() != positions.len() {
+            panic!["mesh needs one uv per position"]
+        }
+        for face in faces.iter() {
+            if face.len() < 3 || face.iter().any(|&v| v >= positions.len()) {
+                panic!["mesh face {:?} is not a polygon of its positions", face]
+            }
+        }
+        Mesh {
+            positions,
+            uvs,
+            faces,
+        }
+    }
+
+    // Flat grid of `nu` by `nv` quads spanning the parallelogram at `corner`
+    // with edges `u` and `v`, with uv running from 0 to 1. A starting point
+    // for displaced terrain.
+    #[allow(dead_code)]
+    pub fn grid(corner: Point3, u: Vec3, v: Vec3, nu: usize, nv: usize) -> Self {
+        let mut positions = Vec::with_capacity((nu + 1) * (nv + 1));
+        let mut uvs = Vec::with_capacity((nu + 1) * (nv + 1));
+        for j in 0..=nv {
+            for i in 0..=nu {
+                let (s, t) = (i as f64 / nu as f64, j as f64 / nv as f64);
+                positions.push(corner + s * u + t * v);
+                uvs.push((s, t));
+            }
+        }
+        let index = |i: usize, j: usize| j * (nu + 1) + i;
+        let faces = (0..nv)
+            .flat_map(|j| {
+                (0..nu).map(move |i| {
+                    vec![
+                        index(i, j),
+                        index(i + 1, j),
+                        index(i + 1, j + 1),
+                        index(i, j + 1),
+                    ]
+                })
+            })
+            .collect();
+        Mesh {
+            positions,
+            uvs,
+            faces,
+        }
+    }
+
+    // Applies `levels` rounds of Catmull-Clark subdivision. Any polygon mesh
+    // is accepted and the result is all quads. Boundary edges are kept as
+    // cubic B-spline curves. Texture coordinates are interpolated linearly.
+    #[allow(dead_code)]
+    pub fn subdivide(&self, levels: usize) -> Mesh {
+        (0..levels).fold(self.clone(), |mesh, _| mesh.catmull_clark())
+    }
+
+    fn catmull_clark(&self) -> Mesh {
+        let nv = self.positions.len();
+        let average = |points: &mut dyn Iterator<Item = Vec3>| {
+            let (sum, n) = points.fold((Vec3::default(), 0), |(s, n), p| (s + p, n + 1));
+            sum / n as f64
+        };
+        let average_uv = |uvs: &[(f64, f64)]| {
+            let n = uvs.len() as f64;
+            let (u, v) = uvs
+                .iter()
+                .fold((0.0, 0.0), |(u, v), uv| (u + uv.0, v + uv.1));
+            (u / n, v / n)
+        };
+
+        let face_points: Vec<Vec3> = self
+            .faces
+            .iter()
+            .map(|f| average(&mut f.iter().map(|&i| self.positions[i])))
+            .collect();
+
+        // Edges keyed by their sorted end points, with the faces around them.
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        let mut edge_list = Vec::new();
+        for (fi, face) in self.faces.iter().enumerate() {
+            for k in 0..face.len() {
+                let (a, b) = (face[k], face[(k + 1) % face.len()]);
+                let key = (a.min(b), a.max(b));
+                edges
+                    .entry(key)
+                    .or_insert_with(|| {
+                        edge_list.push(key);
+                        Vec::new()
+                    })
+                    .push(fi);
+            }
+        }
+        let edge_index: HashMap<(usize, usize), usize> = edge_list
+            .iter()
+            .enumerate()
+            .map(|(i, &key)| (key, i))
+            .collect();
+
+        let midpoint = |(a, b): (usize, usize)| 0.5 * (self.positions[a] + self.positions[b]);
+        let edge_points: Vec<Vec3> = edge_list
+            .iter()
+            .map(|&key| {
+                let faces = &edges[&key];
+                if faces.len() == 2 {
+                    let (a, b) = key;
+                    0.25 * (self.positions[a]
+                        + self.positions[b]
+                        + face_points[faces[0]]
+                        + face_points[faces[1]])
+                } else {
+                    midpoint(key)
+                }
+            })
+            .collect();
+
+        // Faces and edges around each vertex.
+        let mut vertex_faces = vec![Vec::new(); nv];
+        for (fi, face) in self.faces.iter().enumerate() {
+            for &v in face {
+                vertex_faces[v].push(fi);
+            }
+        }
+        let mut vertex_edges = vec![Vec::new(); nv];
+        for &key in edge_list.iter() {
+            vertex_edges[key.0].push(key);
+            vertex_edges[key.1].push(key);
+        }
+
+        let vertex_points: Vec<Vec3> = (0..nv)
+            .map(|v| {
+                let p = self.positions[v];
+                let boundary: Vec<_> = vertex_edges[v]
+                    .iter()
+                    .filter(|key| edges[key].len() != 2)
+                    .collect();
+                if vertex_edges[v].is_empty() {
+                    p
+                } else if !boundary.is_empty() {
+                    if boundary.len() == 2 {
+                        0.5 * p + 0.25 * (midpoint(*boundary[0]) + midpoint(*boundary[1]))
+                    } else {
+                        // Corners and non-manifold vertices stay put.
+                        p
+                    }
+                } else {
+                    let n = vertex_edges[v].len() as f64;
+                    let f = average(&mut vertex_faces[v].iter().map(|&fi| face_points[fi]));
+                    let r = average(&mut vertex_edges[v].iter().map(|&key| midpoint(key)));
+                    (f + 2.0 * r + (n - 3.0) * p) / n
+                }
+            })
+            .collect();
+
+        // New vertices: original points, then edge points, then face points.
+        let edge_base = nv;
+        let face_base = nv + edge_list.len();
+        let mut positions = vertex_points;
+        positions.extend(edge_points);
+        positions.extend(face_points);
+
+        let mut uvs = self.uvs.clone();
+        uvs.extend(
+            edge_list
+                .iter()
+                .map(|&(a, b)| average_uv(&[self.uvs[a], self.uvs[b]])),
+        );
+        uvs.extend(self.faces.iter().map(|f| {
+            let corners: Vec<_> = f.iter().map(|&i| self.uvs[i]).collect();
+            average_uv(&corners)
+        }));
+
+        let edge_of = |a: usize, b: usize| edge_base + edge_index[&(a.min(b), a.max(b))];
+        let faces = self
+            .faces
+            .iter()
+            .enumerate()
+            .flat_map(|(fi, face)| {
+                let n = face.len();
+                (0..n).map(move |k| {
+                    let prev = face[(k + n - 1) % n];
+                    let v = face[k];
+                    let next = face[(k + 1) % n];
+                    vec![v, edge_of(v, next), face_base + fi, edge_of(prev, v)]
+                })
+            })
+            .collect();
+
+        Mesh {
+            positions,
+            uvs,
+            faces,
+        }
+    }
+
+    // Area-weighted average of the normals of the faces around each vertex.
+    pub fn vertex_normals(&self) -> Vec<Vec3> {
+        let mut normals = vec![Vec3::default(); self.positions.len()];
+        for face in self.faces.iter() {
+            let p0 = self.positions[face[0]];
+            // Fan out from the first vertex; the cross products sum to twice
+            // the polygon's area along its normal.
+            let mut n = Vec3::default();
+            for k in 1..face.len() - 1 {
+                n += cross(
+                    &(self.positions[face[k]] - p0),
+                    &(self.positions[face[k + 1]] - p0),
+                );
+            }
+            for &v in face {
+                normals[v] += n;
+            }
+        }
+        normals
+            .iter()
+            .map(|n| if n.near_zero() { *n } else { unit_vector(n) })
+            .collect()
+    }
+
+    // Moves every vertex along its normal by `scale` times the luminance of
+    // `height` at the vertex. Subdivide first so there are enough vertices to
+    // carry the detail.
+    #[allow(dead_code)]
+    pub fn displace<T: Texture>(&self, height: &T, scale: f64) -> Mesh {
+        let normals = self.vertex_normals();
+        let positions = self
+            .positions
+            .iter()
+            .zip(self.uvs.iter())
+            .zip(normals.iter())
+            .map(|((p, &(u, v)), n)| *p + scale * luminance(&height.value(u, v, p)) * n)
+            .collect();
+        Mesh {
+            positions,
+            uvs: self.uvs.clone(),
+            faces: self.faces.clone(),
+        }
+    }
+
+    // Splits every face into a fan of smooth-shaded triangles sharing one
+    // material, ready for `BVH::new`.
+    #[allow(dead_code)]
+    pub fn triangles<M: Material + 'static>(&self, material: M) -> Vec<Box<dyn Hittable>> {
+        let material = Arc::new(material);
+        let normals = self.vertex_normals();
+        let mut triangles: Vec<Box<dyn Hittable>> = Vec::new();
+        for face in self.faces.iter() {
+            for k in 1..face.len() - 1 {
+                let [a, b, c] = [face[0], face[k], face[k + 1]];
+                let triangle = Triangle::new(
+                    self.positions[a],
+                    self.positions[b],
+                    self.positions[c],
+                    material.clone(),
+                )
+                .with_uvs([self.uvs[a], self.uvs[b], self.uvs[c]]);
+                let triangle = if [a, b, c].iter().all(|&i| !normals[i].near_zero()) {
+                    triangle.with_normals([normals[a], normals[b], normals[c]])
+                } else {
+                    triangle
+                };
+                triangles.push(Box::new(triangle));
+            }
+        }
+        triangles
+    }
+}
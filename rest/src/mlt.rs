@@ -0,0 +1,132 @@
+use crate::camera::CameraModel;
+use crate::color::luminance;
+use crate::film::Film;
+use crate::integrator::Integrator;
+use crate::sampler::{self, replay, PrimarySampler};
+use crate::vec3::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::sync::Mutex;
+
+// Width of small-step mutations in primary sample space.
+const SIGMA: f64 = 0.01;
+const LARGE_STEP_PROBABILITY: f64 = 0.3;
+
+// Primary-sample-space Metropolis light transport (Kelemen et al.). A path
+// is a point in the unit hypercube of random numbers the path tracer
+// consumes, the first two picking the image position. Chains of mutations
+// wander through it with density proportional to the path's luminance,
+// staying near the rare paths that carry light through narrow openings once
+// found, and every mutation is splatted where its path lands.
+//
+// The overall brightness `b` is estimated from `bootstrap_samples`
+// independent paths, which also seed the `chains`, run in parallel.
+pub struct Mlt<'a> {
+    pub camera: &'a dyn CameraModel,
+    pub integrator: &'a dyn Integrator,
+    pub bootstrap_samples: usize,
+    pub chains: usize,
+    // Image size and crop window [x0, x1) by [y0, y1), y running down.
+    pub image_width: usize,
+    pub image_height: usize,
+    pub crop: (usize, usize, usize, usize),
+}
+
+impl<'a> Mlt<'a> {
+    // Splats `mutations_per_pixel` mutations per pixel of the crop window
+    // onto `film`, returning the scale to apply to the splats.
+    pub fn render(&self, film: &Film, mutations_per_pixel: i32) -> f64 {
+        let weights: Vec<f64> = (0..self.bootstrap_samples)
+            .into_par_iter()
+            .map(|i| {
+                let sampler = PrimarySampler::new(i as u64, SIGMA, LARGE_STEP_PROBABILITY);
+                luminance(&self.path(sampler).0 .2)
+            })
+            .collect();
+        let mut cdf = Vec::with_capacity(weights.len());
+        let mut total = 0.0;
+        for w in &weights {
+            total += w;
+            cdf.push(total);
+        }
+        if total <= 0.0 {
+            eprintln!("\nNo light found while bootstrapping.");
+            return 0.0;
+        }
+        let b = total / weights.len() as f64;
+
+        let (x0, x1, y0, y1) = self.crop;
+        let pixels = (x1 - x0) * (y1 - y0);
+        let mutations = pixels as u64 * mutations_per_pixel.max(0) as u64;
+        let chains = self.chains.max(1);
+        let counter = Mutex::new(0);
+        (0..chains).into_par_iter().for_each(|c| {
+            let mut splats = film.splat_buffer();
+            let mut rng = StdRng::seed_from_u64(!(c as u64));
+            // Start where a bootstrap path landed, picked by its luminance.
+            let u = rng.gen::<f64>() * total;
+            let seed = cdf.partition_point(|&w| w <= u).min(cdf.len() - 1);
+            let sampler = PrimarySampler::new(seed as u64, SIGMA, LARGE_STEP_PROBABILITY);
+            let ((mut x, mut y, mut l), mut sampler) = self.path(sampler);
+
+            let first = mutations * c as u64 / chains as u64;
+            let last = mutations * (c as u64 + 1) / chains as u64;
+            for _ in first..last {
+                sampler.start_iteration();
+                let ((px, py, pl), s) = self.path(sampler);
+                sampler = s;
+                let current = luminance(&l);
+                let proposed = luminance(&pl);
+                let accept = if current > 0.0 {
+                    (proposed / current).min(1.0)
+                } else {
+                    1.0
+                };
+                // Both states get their expected share, so rejected
+                // proposals still contribute.
+                if accept > 0.0 {
+                    film.add_splat(&mut splats, px, py, &(pl * (accept / proposed)));
+                }
+                if current > 0.0 {
+                    film.add_splat(&mut splats, x, y, &(l * ((1.0 - accept) / current)));
+                }
+                if rng.gen::<f64>() < accept {
+                    x = px;
+                    y = py;
+                    l = pl;
+                    sampler.accept();
+                } else {
+                    sampler.reject();
+                }
+            }
+            film.merge_splats(&splats);
+            let mut num = counter.lock().unwrap();
+            *num += 1;
+            eprint!("\rChains remaining: {} ", chains - *num);
+        });
+        b * pixels as f64 / mutations.max(1) as f64
+    }
+
+    // Traces the path `sampler` currently stands for, returning where it
+    // lands on the image, y running down, and the radiance it carries.
+    fn path(&self, sampler: PrimarySampler) -> ((f64, f64, Color), PrimarySampler) {
+        replay(sampler, || {
+            let mut rng = sampler::rng();
+            let (x0, x1, y0, y1) = self.crop;
+            let x = x0 as f64 + rng.gen::<f64>() * (x1 - x0) as f64;
+            let y = y0 as f64 + rng.gen::<f64>() * (y1 - y0) as f64;
+            let s = x / (self.image_width as f64 - 1.0);
+            let t = (self.image_height as f64 - y) / (self.image_height as f64 - 1.0);
+            let (r, weight) = self.camera.get_weighted_ray(s, t);
+            let l = if weight > 0.0 {
+                weight * self.integrator.ray_color(&r).0
+            } else {
+                Color::default()
+            };
+            // A broken path would poison the chain it's accepted into.
+            let valid = luminance(&l).is_finite() && l.x() >= 0.0 && l.y() >= 0.0 && l.z() >= 0.0;
+            (x, y, if valid { l } else { Color::default() })
+        })
+    }
+}
@@ -2,7 +2,7 @@ use crate::aabb::*;
 use crate::hittable::*;
 use crate::material::*;
 use crate::ray::Ray;
-use crate::sphere::get_sphere_uv;
+use crate::sphere::{get_sphere_tangents, get_sphere_uv};
 use crate::vec3::*;
 pub struct MovingSphere<M: Material> {
     pub center0: Vec3,
@@ -64,15 +64,10 @@ impl<M: Material> Hittable for MovingSphere<M> {
         let p = r.at(t);
         let outward_normal = (&p - &center) / self.radius;
         let (u, v) = get_sphere_uv(&outward_normal);
-        Some(HitRecord::new(
-            p,
-            &self.material,
-            t,
-            u,
-            v,
-            r,
-            outward_normal,
-        ))
+        let (dpdu, dpdv) = get_sphere_tangents(&outward_normal, self.radius);
+        Some(
+            HitRecord::new(p, &self.material, t, u, v, r, outward_normal).with_tangents(dpdu, dpdv),
+        )
     }
 
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
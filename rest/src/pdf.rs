@@ -1,11 +1,13 @@
 use crate::hittable::Hittable;
 use crate::onb::ONB;
+use crate::ray::Ray;
+use crate::sampler;
 use crate::vec3::*;
 use rand::Rng;
 use std::f64;
 
 pub fn random_cosine_direction() -> Vec3 {
-    let mut rng = rand::thread_rng();
+    let mut rng = sampler::rng();
     let r1 = rng.gen::<f64>();
     let r2 = rng.gen::<f64>();
     let z = (1.0 - r2).sqrt();
@@ -15,13 +17,44 @@ pub fn random_cosine_direction() -> Vec3 {
     Vec3::new(x, y, z)
 }
 
+// Monte Carlo estimate of the integral of `hittable.pdf_value(o, _)` over the
+// sphere of directions. Any light visible from `o` should come out close to 1.
+pub fn integrate_pdf_value(hittable: &dyn Hittable, o: &Vec3, samples: usize) -> f64 {
+    let sum: f64 = (0..samples)
+        .map(|_| hittable.pdf_value(o, &random_unit_vector()))
+        .sum();
+    4.0 * f64::consts::PI * sum / samples as f64
+}
+
+// Solid-angle density of the direction `v` from `o` when points are picked
+// uniformly by area over the surface of `hittable`. Curved shapes can be
+// crossed more than once along `v`, and every crossing adds to the density.
+// Directions grazing the silhouette of a shape sampled this way can miss it
+// by rounding, so `validate_lights` allows this fraction of them.
+pub const SURFACE_PDF_TOLERANCE: f64 = 1e-3;
+
+pub fn surface_pdf_value(hittable: &dyn Hittable, o: &Vec3, v: &Vec3, area: f64) -> f64 {
+    let ray = Ray::new(*o, *v, 0.0);
+    let mut pdf = 0.0;
+    let mut t_min = 0.001;
+    while let Some(hit) = hittable.hit(&ray, t_min, f64::MAX) {
+        let distance_squared = hit.t.powi(2) * v.length_squared();
+        let cosine = dot(v, &hit.normal).abs() / v.length();
+        if cosine > 1e-8 {
+            pdf += distance_squared / (cosine * area);
+        }
+        t_min = hit.t + 0.0001;
+    }
+    pdf
+}
+
 pub enum PDF<'a> {
     Cosine {
         uvw: ONB,
     },
     Hittable {
         origin: Vec3,
-        hittable: &'a Box<dyn Hittable>,
+        hittable: &'a dyn Hittable,
     },
     Mixture {
         p: &'a PDF<'a>,
@@ -36,7 +69,7 @@ impl<'a> PDF<'a> {
         }
     }
 
-    pub fn hittable(hittable: &'a Box<dyn Hittable>, origin: &Vec3) -> Self {
+    pub fn hittable(hittable: &'a dyn Hittable, origin: &Vec3) -> Self {
         PDF::Hittable {
             origin: origin.clone(),
             hittable: hittable,
@@ -67,7 +100,7 @@ impl<'a> PDF<'a> {
             PDF::Cosine { uvw } => uvw.local(&random_cosine_direction()),
             PDF::Hittable { origin, hittable } => hittable.random(origin),
             PDF::Mixture { p, q } => {
-                let mut rng = rand::thread_rng();
+                let mut rng = sampler::rng();
                 if rng.gen::<bool>() {
                     p.generate()
                 } else {
@@ -22,7 +22,7 @@ fn permute(p: &mut [usize], n: usize) {
     }
 }
 
-fn perlin_generate_perm() -> Vec<usize> {
+pub fn perlin_generate_perm() -> Vec<usize> {
     let mut p = Vec::with_capacity(256);
     for i in 0..256 {
         p.push(i);
@@ -101,4 +101,44 @@ impl Perlin {
         }
         f64::abs(accum)
     }
+    // Fractional Brownian motion: signed sum of octaves, roughly in [-1, 1].
+    #[allow(dead_code)]
+    pub fn fbm(&self, p: &Vec3, octaves: usize, lacunarity: f64, gain: f64) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+        let mut norm = 0.0;
+        for _ in 0..octaves {
+            accum += weight * self.noise(&temp_p);
+            norm += weight;
+            weight *= gain;
+            temp_p *= lacunarity;
+        }
+        if norm > 0.0 {
+            accum / norm
+        } else {
+            0.0
+        }
+    }
+
+    // Ridged multifractal: sharp creases where the noise crosses zero, in [0, 1].
+    #[allow(dead_code)]
+    pub fn ridged(&self, p: &Vec3, octaves: usize, lacunarity: f64, gain: f64) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+        let mut norm = 0.0;
+        for _ in 0..octaves {
+            let ridge = 1.0 - self.noise(&temp_p).abs();
+            accum += weight * ridge * ridge;
+            norm += weight;
+            weight *= gain;
+            temp_p *= lacunarity;
+        }
+        if norm > 0.0 {
+            accum / norm
+        } else {
+            0.0
+        }
+    }
 }
@@ -0,0 +1,145 @@
+// Closed-form polynomial root finders after Schwarze, "Cubic and Quartic
+// Roots", Graphics Gems I. Coefficients are in ascending order, so `c[i]`
+// multiplies x^i. Only real roots are returned, in no particular order.
+
+const EPSILON: f64 = 1e-9;
+
+fn is_zero(x: f64) -> bool {
+    x.abs() < EPSILON
+}
+
+pub fn solve_quadratic(c: [f64; 3]) -> Vec<f64> {
+    // Normal form x^2 + 2px + q = 0.
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let d = p * p - q;
+    if is_zero(d) {
+        vec![-p]
+    } else if d < 0.0 {
+        vec![]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![sqrt_d - p, -sqrt_d - p]
+    }
+}
+
+pub fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    // Normal form x^3 + Ax^2 + Bx + C = 0.
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let cc = c[0] / c[3];
+
+    // Substitute x = y - A/3 to eliminate the quadric term: y^3 + 3py + 2q = 0.
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + cc) / 2.0;
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+
+    let mut roots = if is_zero(d) {
+        if is_zero(q) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        // Three real roots.
+        let phi = (-q / (-cb_p).sqrt()).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + std::f64::consts::FRAC_PI_3).cos(),
+            -t * (phi - std::f64::consts::FRAC_PI_3).cos(),
+        ]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    let sub = a / 3.0;
+    roots.iter_mut().for_each(|x| *x -= sub);
+    roots
+}
+
+pub fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    // Normal form x^4 + Ax^3 + Bx^2 + Cx + D = 0.
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    // Substitute x = y - A/4 to eliminate the cubic term: y^4 + py^2 + qy + r = 0.
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + cc;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * cc / 4.0 + d;
+
+    let mut roots = if is_zero(r) {
+        // No absolute term: y(y^3 + py + q) = 0.
+        let mut roots = solve_cubic([q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else if is_zero(q) {
+        // Biquadratic: solve for y^2 directly.
+        solve_quadratic([r, p, 1.0])
+            .into_iter()
+            .filter(|&w| w >= 0.0)
+            .flat_map(|w| vec![w.sqrt(), -w.sqrt()])
+            .collect()
+    } else {
+        // Take one root of the resolvent cubic to split into two quadrics.
+        let z = solve_cubic([r * p / 2.0 - q * q / 8.0, -r, -p / 2.0, 1.0])[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if u.abs() < 1e-6 {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return vec![];
+        };
+        let v = if v.abs() < 1e-6 {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return vec![];
+        };
+        let v = if q < 0.0 { -v } else { v };
+        let mut roots = solve_quadratic([z - u, v, 1.0]);
+        roots.extend(solve_quadratic([z + u, -v, 1.0]));
+        roots
+    };
+
+    let sub = a / 4.0;
+    roots.iter_mut().for_each(|x| *x -= sub);
+    roots
+}
+
+// Value and derivative of the polynomial `c` at `x`.
+fn evaluate(c: &[f64], x: f64) -> (f64, f64) {
+    c.iter()
+        .rev()
+        .fold((0.0, 0.0), |(f, df), &ci| (f * x + ci, df * x + f))
+}
+
+// Refines a root of `c` with a few Newton steps, stopping as soon as a step
+// fails to improve it, which happens near double roots.
+pub fn polish_root(c: &[f64], mut x: f64) -> f64 {
+    let (mut f, mut df) = evaluate(c, x);
+    for _ in 0..3 {
+        if df.abs() < EPSILON {
+            break;
+        }
+        let next = x - f / df;
+        let (next_f, next_df) = evaluate(c, next);
+        if next_f.abs() >= f.abs() {
+            break;
+        }
+        x = next;
+        f = next_f;
+        df = next_df;
+    }
+    x
+}
@@ -0,0 +1,105 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler;
+use crate::vec3::*;
+use rand::Rng;
+
+// Parallelogram spanned by edge vectors `u` and `v` from corner `q`, in any
+// orientation.
+#[derive(Clone)]
+pub struct Quad<M: Material> {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    d: f64,
+    w: Vec3,
+    area: f64,
+    material: M,
+}
+
+impl<M: Material> Quad<M> {
+    #[allow(dead_code)]
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: M) -> Self {
+        let n = cross(&u, &v);
+        let normal = unit_vector(&n);
+        Quad {
+            q,
+            u,
+            v,
+            normal,
+            d: dot(&normal, &q),
+            w: n / dot(&n, &n),
+            area: n.length(),
+            material,
+        }
+    }
+}
+
+impl<M: Material> Hittable for Quad<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = dot(&self.normal, ray.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.d - dot(&self.normal, ray.origin())) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = ray.at(t);
+        let planar = p - self.q;
+        let alpha = dot(&self.w, &cross(&planar, &self.v));
+        let beta = dot(&self.w, &cross(&self.u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(
+            HitRecord::new(p, &self.material, t, alpha, beta, ray, self.normal)
+                .with_tangents(self.u, self.v),
+        )
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let corners = [self.q + self.u, self.q + self.v, self.q + self.u + self.v];
+        let mut min = self.q;
+        let mut max = self.q;
+        for c in corners.iter() {
+            for a in 0..3 {
+                min[a] = min[a].min(c[a]);
+                max[a] = max[a].max(c[a]);
+            }
+        }
+        // Pad so that a quad lying in an axis plane still has volume.
+        for a in 0..3 {
+            if max[a] - min[a] < 0.0002 {
+                min[a] -= 0.0001;
+                max[a] += 0.0001;
+            }
+        }
+        Some(AABB::new(min, max))
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        if let Some(hit) = self.hit(&Ray::new(*o, *v, 0.0), 0.001, f64::MAX) {
+            let distance_squared = hit.t.powi(2) * v.length_squared();
+            let cosine = dot(v, &self.normal).abs() / v.length();
+            if cosine > 1e-8 {
+                distance_squared / (cosine * self.area)
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        let mut rng = sampler::rng();
+        let p = self.q + rng.gen::<f64>() * self.u + rng.gen::<f64>() * self.v;
+        p - o
+    }
+}
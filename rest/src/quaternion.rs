@@ -0,0 +1,86 @@
+use crate::rtweekend::degrees_to_radians;
+use crate::vec3::*;
+
+// Unit quaternion for rotations: `w` is the scalar part, `v` the vector part.
+#[derive(Clone, Copy)]
+pub struct Quaternion {
+    pub w: f64,
+    pub v: Vec3,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion {
+            w: 1.0,
+            v: Vec3::default(),
+        }
+    }
+}
+
+impl Quaternion {
+    #[allow(dead_code)]
+    pub fn from_axis_angle(axis: &Vec3, angle: f64) -> Self {
+        let half = 0.5 * degrees_to_radians(angle);
+        Quaternion {
+            w: half.cos(),
+            v: half.sin() * unit_vector(axis),
+        }
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + dot(&self.v, &other.v)
+    }
+
+    fn scaled(&self, s: f64) -> Quaternion {
+        Quaternion {
+            w: s * self.w,
+            v: s * self.v,
+        }
+    }
+
+    fn add(&self, other: &Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w + other.w,
+            v: self.v + other.v,
+        }
+    }
+
+    fn normalized(&self) -> Quaternion {
+        self.scaled(1.0 / self.dot(self).sqrt())
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion {
+            w: self.w,
+            v: -self.v,
+        }
+    }
+
+    pub fn rotate(&self, p: &Vec3) -> Vec3 {
+        // p' = p + 2w(v x p) + 2v x (v x p)
+        let t = 2.0 * cross(&self.v, p);
+        *p + self.w * t + cross(&self.v, &t)
+    }
+
+    // Angle in radians of the rotation taking `self` to `other`.
+    pub fn angle_to(&self, other: &Quaternion) -> f64 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    // Spherical linear interpolation along the shorter arc.
+    pub fn slerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        let mut cos_theta = self.dot(other);
+        let mut other = *other;
+        if cos_theta < 0.0 {
+            other = other.scaled(-1.0);
+            cos_theta = -cos_theta;
+        }
+        if cos_theta > 0.9995 {
+            return self.scaled(1.0 - t).add(&other.scaled(t)).normalized();
+        }
+        let theta = cos_theta.acos();
+        let theta_t = theta * t;
+        let perp = other.add(&self.scaled(-cos_theta)).normalized();
+        self.scaled(theta_t.cos()).add(&perp.scaled(theta_t.sin()))
+    }
+}
@@ -0,0 +1,465 @@
+use crate::camera::{CameraModel, Shutter};
+use crate::ray::Ray;
+use crate::rtweekend::random_double;
+use crate::vec3::*;
+use rayon::prelude::*;
+use std::fs;
+
+// One row of a lens prescription: a spherical interface (or the aperture stop
+// when `curvature_radius` is zero) followed by `thickness` of a medium with
+// index `eta` toward the film. Rows run from the front element to the rear.
+#[derive(Clone)]
+pub struct LensElement {
+    pub curvature_radius: f64,
+    pub thickness: f64,
+    pub eta: f64,
+    pub aperture_radius: f64,
+}
+
+// How a lens prescription is mounted on a `RealisticCamera`. Lengths are in
+// millimetres except `focus_dist`, which is in world units.
+pub struct LensMount {
+    pub elements: Vec<LensElement>,
+    pub world_per_mm: f64,
+    pub film_diagonal: f64,
+    // Diameter the aperture stop is closed down to.
+    pub aperture_diameter: f64,
+    pub focus_dist: f64,
+}
+
+const PUPIL_BINS: usize = 64;
+// Tries `get_ray` makes at a film point before giving up on the lens
+// letting any light through there.
+const MAX_RAY_TRIES: usize = 64;
+const PUPIL_SAMPLES: usize = 128;
+
+// Parses whitespace separated `radius thickness ior aperture-diameter` rows in
+// millimetres, as used by pbrt's lens files. Lines starting with '#' are skipped.
+pub fn parse_prescription(text: &str) -> Vec<LensElement> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let cols: Vec<f64> = line
+                .split_whitespace()
+                .map(|c| c.parse().expect("Invalid number in lens prescription"))
+                .collect();
+            if cols.len() != 4 {
+                panic!["lens prescription rows need 4 columns: {}", line]
+            }
+            LensElement {
+                curvature_radius: cols[0],
+                thickness: cols[1],
+                eta: cols[2],
+                aperture_radius: cols[3] / 2.0,
+            }
+        })
+        .collect()
+}
+
+pub fn load_prescription(path: &str) -> Vec<LensElement> {
+    parse_prescription(&fs::read_to_string(path).expect("Unable to read lens file"))
+}
+
+fn air_if_zero(eta: f64) -> f64 {
+    if eta == 0.0 {
+        1.0
+    } else {
+        eta
+    }
+}
+
+fn refract(wi: &Vec3, n: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_theta_i = dot(n, wi);
+    let sin2_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0);
+    let sin2_theta_t = eta * eta * sin2_theta_i;
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(eta * -wi + (eta * cos_theta_i - cos_theta_t) * n)
+}
+
+fn intersect_spherical(radius: f64, z_center: f64, o: &Vec3, d: &Vec3) -> Option<(f64, Vec3)> {
+    let oc = *o - Vec3::new(0.0, 0.0, z_center);
+    let a = d.length_squared();
+    let half_b = dot(&oc, d);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+    let t0 = (-half_b - sqrtd) / a;
+    let t1 = (-half_b + sqrtd) / a;
+    let use_closer = (d.z() > 0.0) ^ (radius < 0.0);
+    let t = if use_closer { t0 } else { t1 };
+    if t < 0.0 {
+        return None;
+    }
+    let mut n = unit_vector(&(oc + t * *d));
+    if dot(&n, &-d) < 0.0 {
+        n = -n;
+    }
+    Some((t, n))
+}
+
+// Radical inverse in base 2 and 3, for stratified exit pupil probing.
+fn radical_inverse(base: usize, mut i: usize) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv = inv_base;
+    let mut result = 0.0;
+    while i > 0 {
+        result += (i % base) as f64 * inv;
+        i /= base;
+        inv *= inv_base;
+    }
+    result
+}
+
+#[derive(Clone, Copy)]
+struct Bounds2 {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl Bounds2 {
+    fn empty() -> Self {
+        Bounds2 {
+            min: (f64::MAX, f64::MAX),
+            max: (f64::MIN, f64::MIN),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.min.0 > self.max.0 || self.min.1 > self.max.1
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.min.0 && x <= self.max.0 && y >= self.min.1 && y <= self.max.1
+    }
+
+    fn extend(&mut self, x: f64, y: f64) {
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0) * (self.max.1 - self.min.1)
+    }
+}
+
+// Camera that traces rays through a stack of spherical lens elements, after
+// pbrt's RealisticCamera. The film sits at `lookfrom`; lens coordinates are
+// millimetres scaled by `world_per_mm`. Rays are sampled over a precomputed
+// bound of the exit pupil for each film radius, so vignetting shows up as
+// zero-weight samples instead of wasted traces everywhere on the rear element.
+pub struct RealisticCamera {
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    elements: Vec<LensElement>,
+    film_width: f64,
+    film_height: f64,
+    exit_pupil_bounds: Vec<Bounds2>,
+    shutter: Shutter,
+    time0: f64,
+    time1: f64,
+}
+
+impl RealisticCamera {
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        lens: LensMount,
+        aspect_ratio: f64,
+        time0: f64,
+        time1: f64,
+    ) -> Self {
+        let LensMount {
+            mut elements,
+            world_per_mm,
+            film_diagonal,
+            aperture_diameter,
+            focus_dist,
+        } = lens;
+        if elements.is_empty() {
+            panic!["lens prescription has no elements"]
+        }
+        for e in elements.iter_mut() {
+            e.curvature_radius *= world_per_mm;
+            e.thickness *= world_per_mm;
+            e.aperture_radius *= world_per_mm;
+            if e.curvature_radius == 0.0 {
+                e.aperture_radius = e
+                    .aperture_radius
+                    .min(world_per_mm * aperture_diameter / 2.0);
+            }
+        }
+        let diagonal = film_diagonal * world_per_mm;
+        let film_width = diagonal / (1.0 + 1.0 / (aspect_ratio * aspect_ratio)).sqrt();
+        let film_height = film_width / aspect_ratio;
+
+        let w = unit_vector(&(lookfrom - lookat));
+        let u = unit_vector(&cross(&vup, &w));
+        let v = cross(&w, &u);
+
+        let mut camera = RealisticCamera {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            elements,
+            film_width,
+            film_height,
+            exit_pupil_bounds: Vec::new(),
+            shutter: Shutter::Uniform,
+            time0,
+            time1,
+        };
+        let rear = camera.focus_thick_lens(focus_dist);
+        camera.elements.last_mut().unwrap().thickness = rear;
+
+        let half_diagonal = diagonal / 2.0;
+        camera.exit_pupil_bounds = (0..PUPIL_BINS)
+            .into_par_iter()
+            .map(|i| {
+                let r0 = i as f64 / PUPIL_BINS as f64 * half_diagonal;
+                let r1 = (i + 1) as f64 / PUPIL_BINS as f64 * half_diagonal;
+                camera.bound_exit_pupil(r0, r1)
+            })
+            .collect();
+        camera
+    }
+
+    pub fn with_shutter(mut self, shutter: Shutter) -> Self {
+        self.shutter = shutter;
+        self
+    }
+
+    fn lens_rear_z(&self) -> f64 {
+        self.elements.last().unwrap().thickness
+    }
+
+    fn lens_front_z(&self) -> f64 {
+        self.elements.iter().map(|e| e.thickness).sum()
+    }
+
+    fn rear_element_radius(&self) -> f64 {
+        self.elements.last().unwrap().aperture_radius
+    }
+
+    // Camera space has +z toward the scene and the film at z = 0; the lens
+    // elements are laid out in a mirrored space with the scene toward -z.
+    fn trace_from_film(&self, o: &Vec3, d: &Vec3) -> Option<(Vec3, Vec3)> {
+        let mut o = Vec3::new(o.x(), o.y(), -o.z());
+        let mut d = Vec3::new(d.x(), d.y(), -d.z());
+        let mut element_z = 0.0;
+        for i in (0..self.elements.len()).rev() {
+            let element = &self.elements[i];
+            element_z -= element.thickness;
+            let is_stop = element.curvature_radius == 0.0;
+            let (t, n) = if is_stop {
+                if d.z() >= 0.0 {
+                    return None;
+                }
+                ((element_z - o.z()) / d.z(), Vec3::default())
+            } else {
+                let z_center = element_z + element.curvature_radius;
+                intersect_spherical(element.curvature_radius, z_center, &o, &d)?
+            };
+            let p = o + t * d;
+            if p.x() * p.x() + p.y() * p.y() > element.aperture_radius.powi(2) {
+                return None;
+            }
+            o = p;
+            if !is_stop {
+                let eta_i = air_if_zero(element.eta);
+                let eta_t = if i > 0 {
+                    air_if_zero(self.elements[i - 1].eta)
+                } else {
+                    1.0
+                };
+                d = refract(&unit_vector(&-d), &n, eta_i / eta_t)?;
+            }
+        }
+        Some((
+            Vec3::new(o.x(), o.y(), -o.z()),
+            Vec3::new(d.x(), d.y(), -d.z()),
+        ))
+    }
+
+    fn trace_from_scene(&self, o: &Vec3, d: &Vec3) -> Option<(Vec3, Vec3)> {
+        let mut o = Vec3::new(o.x(), o.y(), -o.z());
+        let mut d = Vec3::new(d.x(), d.y(), -d.z());
+        let mut element_z = -self.lens_front_z();
+        for i in 0..self.elements.len() {
+            let element = &self.elements[i];
+            let is_stop = element.curvature_radius == 0.0;
+            let (t, n) = if is_stop {
+                ((element_z - o.z()) / d.z(), Vec3::default())
+            } else {
+                let z_center = element_z + element.curvature_radius;
+                intersect_spherical(element.curvature_radius, z_center, &o, &d)?
+            };
+            let p = o + t * d;
+            if p.x() * p.x() + p.y() * p.y() > element.aperture_radius.powi(2) {
+                return None;
+            }
+            o = p;
+            if !is_stop {
+                let eta_i = if i > 0 {
+                    air_if_zero(self.elements[i - 1].eta)
+                } else {
+                    1.0
+                };
+                let eta_t = air_if_zero(element.eta);
+                d = refract(&unit_vector(&-d), &n, eta_i / eta_t)?;
+            }
+            element_z += element.thickness;
+        }
+        Some((
+            Vec3::new(o.x(), o.y(), -o.z()),
+            Vec3::new(d.x(), d.y(), -d.z()),
+        ))
+    }
+
+    // Principal plane and focal point z of a paraxial ray pair.
+    fn cardinal_points(in_o: &Vec3, out_o: &Vec3, out_d: &Vec3) -> (f64, f64) {
+        let tf = -out_o.x() / out_d.x();
+        let fz = -(*out_o + tf * *out_d).z();
+        let tp = (in_o.x() - out_o.x()) / out_d.x();
+        let pz = -(*out_o + tp * *out_d).z();
+        (pz, fz)
+    }
+
+    // Distance from the rear element to the film that focuses at `focus_dist`,
+    // using a thick lens approximation of the element stack.
+    fn focus_thick_lens(&self, focus_dist: f64) -> f64 {
+        let x = 0.001 * (self.film_width.powi(2) + self.film_height.powi(2)).sqrt();
+        let scene_o = Vec3::new(x, 0.0, self.lens_front_z() + 1.0);
+        let (film_o, film_d) = self
+            .trace_from_scene(&scene_o, &Vec3::new(0.0, 0.0, -1.0))
+            .expect("Unable to trace paraxial ray through lens from scene side");
+        let (pz0, fz0) = RealisticCamera::cardinal_points(&scene_o, &film_o, &film_d);
+
+        let film_o2 = Vec3::new(x, 0.0, self.lens_rear_z() - 1.0);
+        let (scene_o2, scene_d2) = self
+            .trace_from_film(&film_o2, &Vec3::new(0.0, 0.0, 1.0))
+            .expect("Unable to trace paraxial ray through lens from film side");
+        let (pz1, _) = RealisticCamera::cardinal_points(&film_o2, &scene_o2, &scene_d2);
+
+        let f = fz0 - pz0;
+        let z = -focus_dist;
+        let c = (pz1 - z - pz0) * (pz1 - z - 4.0 * f - pz0);
+        if c <= 0.0 {
+            panic!["focus distance {} is too short for this lens", focus_dist]
+        }
+        let delta = 0.5 * (pz1 - z + pz0 - c.sqrt());
+        self.lens_rear_z() + delta
+    }
+
+    fn bound_exit_pupil(&self, film_x0: f64, film_x1: f64) -> Bounds2 {
+        let n = PUPIL_SAMPLES * PUPIL_SAMPLES;
+        let rear_radius = 1.5 * self.rear_element_radius();
+        let rear_z = self.lens_rear_z();
+        let mut bounds = Bounds2::empty();
+        for i in 0..n {
+            let fx = film_x0 + (i as f64 + 0.5) / n as f64 * (film_x1 - film_x0);
+            let film = Vec3::new(fx, 0.0, 0.0);
+            let x = (2.0 * radical_inverse(2, i) - 1.0) * rear_radius;
+            let y = (2.0 * radical_inverse(3, i) - 1.0) * rear_radius;
+            if bounds.contains(x, y)
+                || self
+                    .trace_from_film(&film, &(Vec3::new(x, y, rear_z) - film))
+                    .is_some()
+            {
+                bounds.extend(x, y);
+            }
+        }
+        if bounds.is_empty() {
+            return Bounds2 {
+                min: (-rear_radius, -rear_radius),
+                max: (rear_radius, rear_radius),
+            };
+        }
+        let pad = 2.0 * 2.0 * 2f64.sqrt() * rear_radius / PUPIL_SAMPLES as f64;
+        Bounds2 {
+            min: (bounds.min.0 - pad, bounds.min.1 - pad),
+            max: (bounds.max.0 + pad, bounds.max.1 + pad),
+        }
+    }
+
+    // Samples a point on the rear element inside the exit pupil bound for the
+    // film point, returning it with the area of the bound.
+    fn sample_exit_pupil(&self, fx: f64, fy: f64) -> (Vec3, f64) {
+        let r_film = (fx * fx + fy * fy).sqrt();
+        let half_diagonal = 0.5 * (self.film_width.powi(2) + self.film_height.powi(2)).sqrt();
+        let bin = ((r_film / half_diagonal * PUPIL_BINS as f64) as usize).min(PUPIL_BINS - 1);
+        let bounds = &self.exit_pupil_bounds[bin];
+        let x = bounds.min.0 + random_double() * (bounds.max.0 - bounds.min.0);
+        let y = bounds.min.1 + random_double() * (bounds.max.1 - bounds.min.1);
+        let (sin_theta, cos_theta) = if r_film != 0.0 {
+            (fy / r_film, fx / r_film)
+        } else {
+            (0.0, 1.0)
+        };
+        (
+            Vec3::new(
+                cos_theta * x - sin_theta * y,
+                sin_theta * x + cos_theta * y,
+                self.lens_rear_z(),
+            ),
+            bounds.area(),
+        )
+    }
+
+    fn to_world(&self, v: &Vec3) -> Vec3 {
+        v.x() * self.u + v.y() * self.v - v.z() * self.w
+    }
+}
+
+impl CameraModel for RealisticCamera {
+    // Where the lens blocks every ray from the film point, gives up after
+    // `MAX_RAY_TRIES` and returns the last, blocked ray.
+    fn get_ray(&self, s: f64, t: f64) -> Ray {
+        let (mut ray, mut weight) = self.get_weighted_ray(s, t);
+        for _ in 1..MAX_RAY_TRIES {
+            if weight > 0.0 {
+                break;
+            }
+            (ray, weight) = self.get_weighted_ray(s, t);
+        }
+        ray
+    }
+
+    fn get_weighted_ray(&self, s: f64, t: f64) -> (Ray, f64) {
+        // The lens inverts the image, so the film point is mirrored.
+        let fx = -(s - 0.5) * self.film_width;
+        let fy = -(t - 0.5) * self.film_height;
+        let film = Vec3::new(fx, fy, 0.0);
+        let (rear, area) = self.sample_exit_pupil(fx, fy);
+        let film_dir = rear - film;
+        let time = self.sample_time();
+        match self.trace_from_film(&film, &film_dir) {
+            Some((o, d)) => {
+                let cos_theta = unit_vector(&film_dir).z();
+                let cos4_theta = cos_theta.powi(4);
+                let weight = cos4_theta * area / self.exit_pupil_bounds[0].area();
+                let ray = Ray::new(
+                    self.origin + self.to_world(&o),
+                    unit_vector(&self.to_world(&d)),
+                    time,
+                );
+                (ray, weight)
+            }
+            None => (Ray::new(self.origin, -self.w, time), 0.0),
+        }
+    }
+
+    fn sample_time(&self) -> f64 {
+        self.shutter.sample(self.time0, self.time1)
+    }
+}
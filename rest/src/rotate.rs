@@ -5,6 +5,7 @@ use crate::vec3::*;
 use std::f64;
 
 #[allow(dead_code)]
+#[derive(Clone, Copy)]
 pub enum Axis {
     X,
     Y,
@@ -79,33 +80,39 @@ impl<H: Hittable> Rotate<H> {
             bbox,
         }
     }
+
+    // Rotates a vector from object space back into world space.
+    fn to_world(&self, v: &Vec3) -> Vec3 {
+        let (_, a_axis, b_axis) = get_axis(&self.axis);
+        let mut rotated = *v;
+        rotated[a_axis] = self.cos_theta * v[a_axis] - self.sin_theta * v[b_axis];
+        rotated[b_axis] = self.sin_theta * v[a_axis] + self.cos_theta * v[b_axis];
+        rotated
+    }
+
+    // Rotates a vector from world space into object space.
+    fn to_object(&self, v: &Vec3) -> Vec3 {
+        let (_, a_axis, b_axis) = get_axis(&self.axis);
+        let mut rotated = *v;
+        rotated[a_axis] = self.cos_theta * v[a_axis] + self.sin_theta * v[b_axis];
+        rotated[b_axis] = -self.sin_theta * v[a_axis] + self.cos_theta * v[b_axis];
+        rotated
+    }
 }
 
 impl<H: Hittable> Hittable for Rotate<H> {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let (_, a_axis, b_axis) = get_axis(&self.axis);
-        let mut origin = ray.origin().clone();
-        let mut direction = ray.direction().clone();
-        origin[a_axis] =
-            self.cos_theta * ray.origin()[a_axis] + self.sin_theta * ray.origin()[b_axis];
-        origin[b_axis] =
-            -self.sin_theta * ray.origin()[a_axis] + self.cos_theta * ray.origin()[b_axis];
-        direction[a_axis] =
-            self.cos_theta * ray.direction()[a_axis] + self.sin_theta * ray.direction()[b_axis];
-        direction[b_axis] =
-            -self.sin_theta * ray.direction()[a_axis] + self.cos_theta * ray.direction()[b_axis];
-        let rotated_ray = Ray::new(origin, direction, ray.time());
+        let rotated_ray = Ray::new(
+            self.to_object(ray.origin()),
+            self.to_object(ray.direction()),
+            ray.time(),
+        );
         self.hitable.hit(&rotated_ray, t_min, t_max).map(|mut hit| {
-            let mut p = hit.p;
-            let mut normal = hit.normal;
-            p[a_axis] = self.cos_theta * hit.p[a_axis] - self.sin_theta * hit.p[b_axis];
-            p[b_axis] = self.sin_theta * hit.p[a_axis] + self.cos_theta * hit.p[b_axis];
-            normal[a_axis] =
-                self.cos_theta * hit.normal[a_axis] - self.sin_theta * hit.normal[b_axis];
-            normal[b_axis] =
-                self.sin_theta * hit.normal[a_axis] + self.cos_theta * hit.normal[b_axis];
-            hit.p = p;
-            hit.normal = normal;
+            hit.p = self.to_world(&hit.p);
+            hit.normal = self.to_world(&hit.normal);
+            hit.shading_normal = self.to_world(&hit.shading_normal);
+            hit.dpdu = self.to_world(&hit.dpdu);
+            hit.dpdv = self.to_world(&hit.dpdv);
             hit
         })
     }
@@ -113,4 +120,17 @@ impl<H: Hittable> Hittable for Rotate<H> {
     fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
         self.bbox.clone()
     }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        self.hitable
+            .pdf_value(&self.to_object(o), &self.to_object(v))
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        self.to_world(&self.hitable.random(&self.to_object(o)))
+    }
+
+    fn sampling_tolerance(&self) -> f64 {
+        self.hitable.sampling_tolerance()
+    }
 }
@@ -1,4 +1,4 @@
-use rand::distributions::uniform::SampleRange;
+use crate::sampler;
 use rand::Rng;
 // Constants
 pub const INFINITY: f64 = f64::INFINITY;
@@ -9,9 +9,5 @@ pub fn degrees_to_radians(degrees: f64) -> f64 {
 }
 
 pub fn random_double() -> f64 {
-    rand::thread_rng().gen()
-}
-
-pub fn random_double_range<R: SampleRange<f64>>(range: R) -> f64 {
-    rand::thread_rng().gen_range(range)
+    sampler::rng().gen()
 }
@@ -0,0 +1,147 @@
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::cell::RefCell;
+use std::f64::consts::PI;
+
+thread_local! {
+    static PRIMARY: RefCell<Option<PrimarySampler>> = const { RefCell::new(None) };
+}
+
+// Random numbers for sampling paths. Draws from the thread's generator,
+// unless `replay` has installed a `PrimarySampler` on this thread, in which
+// case every number comes from its vector of primary samples so the same
+// path can be traced again after mutating them.
+pub struct SampleRng;
+
+pub fn rng() -> SampleRng {
+    SampleRng
+}
+
+impl RngCore for SampleRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        PRIMARY.with(|primary| match primary.borrow_mut().as_mut() {
+            // The top bits are what `gen::<f64>()` and friends look at.
+            Some(sampler) => (sampler.next() * 2f64.powi(64)) as u64,
+            None => rand::thread_rng().next_u64(),
+        })
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+// Runs `f` with `sampler` supplying this thread's `rng()`, then hands the
+// sampler back.
+pub fn replay<R>(sampler: PrimarySampler, f: impl FnOnce() -> R) -> (R, PrimarySampler) {
+    PRIMARY.with(|primary| *primary.borrow_mut() = Some(sampler));
+    let result = f();
+    let sampler = PRIMARY.with(|primary| primary.borrow_mut().take()).unwrap();
+    (result, sampler)
+}
+
+#[derive(Clone, Copy, Default)]
+struct PrimarySample {
+    value: f64,
+    // Iteration the value was last brought up to date in.
+    modified: u64,
+    backup: f64,
+    modified_backup: u64,
+}
+
+// Point in primary sample space for Metropolis sampling, mutated either by
+// a large step, which draws every coordinate afresh, or a small step, which
+// perturbs each by a normal of width `sigma`, wrapping around [0, 1).
+// Coordinates are created and brought up to date lazily as the path asks
+// for them, so paths of any length can be sampled.
+pub struct PrimarySampler {
+    rng: StdRng,
+    sigma: f64,
+    large_step_probability: f64,
+    x: Vec<PrimarySample>,
+    iteration: u64,
+    large_step: bool,
+    last_large_step: u64,
+    index: usize,
+}
+
+impl PrimarySampler {
+    pub fn new(seed: u64, sigma: f64, large_step_probability: f64) -> Self {
+        PrimarySampler {
+            rng: StdRng::seed_from_u64(seed),
+            sigma,
+            large_step_probability,
+            x: Vec::new(),
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            index: 0,
+        }
+    }
+
+    // Begins a new proposal, from the first coordinate.
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.gen::<f64>() < self.large_step_probability;
+        self.index = 0;
+    }
+
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    // Restores the coordinates the rejected proposal changed.
+    pub fn reject(&mut self) {
+        for xi in self.x.iter_mut() {
+            if xi.modified == self.iteration {
+                xi.value = xi.backup;
+                xi.modified = xi.modified_backup;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    fn next(&mut self) -> f64 {
+        if self.index >= self.x.len() {
+            self.x.push(PrimarySample::default());
+        }
+        let i = self.index;
+        self.index += 1;
+        let xi = &mut self.x[i];
+        // A coordinate untouched since the last accepted large step starts
+        // from a fresh value.
+        if xi.modified < self.last_large_step {
+            xi.value = self.rng.gen();
+            xi.modified = self.last_large_step;
+        }
+        xi.backup = xi.value;
+        xi.modified_backup = xi.modified;
+        if self.large_step {
+            xi.value = self.rng.gen();
+        } else {
+            // Catch up on every small step this coordinate missed at once.
+            let steps = (self.iteration - xi.modified) as f64;
+            let u1: f64 = 1.0 - self.rng.gen::<f64>();
+            let u2: f64 = self.rng.gen();
+            let normal = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+            xi.value += normal * self.sigma * steps.sqrt();
+            xi.value -= xi.value.floor();
+        }
+        xi.modified = self.iteration;
+        xi.value
+    }
+}
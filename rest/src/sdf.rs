@@ -0,0 +1,258 @@
+use crate::aabb::AABB;
+use crate::hittable::*;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sphere::get_sphere_uv;
+use crate::vec3::*;
+
+// Signed distance to a surface: negative inside, positive outside. Closures
+// taking a point work as distance functions too.
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: &Point3) -> f64;
+}
+
+impl<F: Fn(&Point3) -> f64 + Send + Sync> Sdf for F {
+    fn distance(&self, p: &Point3) -> f64 {
+        self(p)
+    }
+}
+
+#[derive(Clone)]
+pub struct SdfSphere {
+    center: Point3,
+    radius: f64,
+}
+
+impl SdfSphere {
+    #[allow(dead_code)]
+    pub fn new(center: Point3, radius: f64) -> Self {
+        SdfSphere { center, radius }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: &Point3) -> f64 {
+        (p - &self.center).length() - self.radius
+    }
+}
+
+// Box with the given half extents, its edges rounded off by `rounding`.
+#[derive(Clone)]
+pub struct SdfBox {
+    center: Point3,
+    half_extents: Vec3,
+    rounding: f64,
+}
+
+impl SdfBox {
+    #[allow(dead_code)]
+    pub fn new(center: Point3, half_extents: Vec3, rounding: f64) -> Self {
+        SdfBox {
+            center,
+            half_extents,
+            rounding,
+        }
+    }
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: &Point3) -> f64 {
+        let mut q = p - &self.center;
+        for a in 0..3 {
+            q[a] = q[a].abs() - self.half_extents[a] + self.rounding;
+        }
+        let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0));
+        let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+        outside.length() + inside - self.rounding
+    }
+}
+
+// Torus in the xz plane, as `Torus`.
+#[derive(Clone)]
+pub struct SdfTorus {
+    center: Point3,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl SdfTorus {
+    #[allow(dead_code)]
+    pub fn new(center: Point3, major_radius: f64, minor_radius: f64) -> Self {
+        SdfTorus {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: &Point3) -> f64 {
+        let q = p - &self.center;
+        let ring = (q.x() * q.x() + q.z() * q.z()).sqrt() - self.major_radius;
+        (ring * ring + q.y() * q.y()).sqrt() - self.minor_radius
+    }
+}
+
+// Union that blends the two surfaces together within distance `k`.
+pub struct SmoothUnion<A: Sdf, B: Sdf> {
+    a: A,
+    b: B,
+    k: f64,
+}
+
+impl<A: Sdf, B: Sdf> SmoothUnion<A, B> {
+    #[allow(dead_code)]
+    pub fn new(a: A, b: B, k: f64) -> Self {
+        SmoothUnion { a, b, k }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, p: &Point3) -> f64 {
+        let da = self.a.distance(p);
+        let db = self.b.distance(p);
+        if self.k <= 0.0 {
+            return da.min(db);
+        }
+        let h = (0.5 + 0.5 * (db - da) / self.k).clamp(0.0, 1.0);
+        db + h * (da - db) - self.k * h * (1.0 - h)
+    }
+}
+
+// Repeats a shape forever with the given period along each axis. A zero
+// period leaves that axis alone.
+pub struct Repeat<S: Sdf> {
+    sdf: S,
+    period: Vec3,
+}
+
+impl<S: Sdf> Repeat<S> {
+    #[allow(dead_code)]
+    pub fn new(sdf: S, period: Vec3) -> Self {
+        Repeat { sdf, period }
+    }
+}
+
+impl<S: Sdf> Sdf for Repeat<S> {
+    fn distance(&self, p: &Point3) -> f64 {
+        let mut q = *p;
+        for a in 0..3 {
+            if self.period[a] > 0.0 {
+                q[a] -= self.period[a] * (q[a] / self.period[a]).round();
+            }
+        }
+        self.sdf.distance(&q)
+    }
+}
+
+// Twists a shape around the y axis by `rate` radians per unit of height.
+// This stretches distances, so trace it with a step scale below one.
+pub struct Twist<S: Sdf> {
+    sdf: S,
+    rate: f64,
+}
+
+impl<S: Sdf> Twist<S> {
+    #[allow(dead_code)]
+    pub fn new(sdf: S, rate: f64) -> Self {
+        Twist { sdf, rate }
+    }
+}
+
+impl<S: Sdf> Sdf for Twist<S> {
+    fn distance(&self, p: &Point3) -> f64 {
+        let (sin, cos) = (self.rate * p.y()).sin_cos();
+        let q = Vec3::new(cos * p.x() - sin * p.z(), p.y(), sin * p.x() + cos * p.z());
+        self.sdf.distance(&q)
+    }
+}
+
+// Implicit surface found by sphere tracing a distance function. Tracing is
+// confined to `bbox`, which must contain the whole surface.
+pub struct SdfShape<S: Sdf, M: Material> {
+    sdf: S,
+    bbox: AABB,
+    material: M,
+    epsilon: f64,
+    step_scale: f64,
+}
+
+impl<S: Sdf, M: Material> SdfShape<S, M> {
+    #[allow(dead_code)]
+    pub fn new(sdf: S, bbox: AABB, material: M) -> Self {
+        let epsilon = 1e-5 * (bbox.max - bbox.min).length();
+        SdfShape {
+            sdf,
+            bbox,
+            material,
+            epsilon,
+            step_scale: 1.0,
+        }
+    }
+
+    // Fraction of the distance bound to step each time, for distance
+    // functions that overestimate (twists, displacements).
+    #[allow(dead_code)]
+    pub fn with_step_scale(mut self, step_scale: f64) -> Self {
+        self.step_scale = step_scale;
+        self
+    }
+
+    // Gradient of the distance from four samples on a tetrahedron.
+    fn normal(&self, p: &Point3) -> Vec3 {
+        let h = self.epsilon;
+        let offsets = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ];
+        let gradient = offsets.iter().fold(Vec3::default(), |sum, k| {
+            sum + self.sdf.distance(&(p + &(h * k))) * k
+        });
+        unit_vector(&gradient)
+    }
+}
+
+impl<S: Sdf, M: Material> Hittable for SdfShape<S, M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        const MAX_STEPS: usize = 512;
+        let (t_enter, t_exit) = self.bbox.interval(ray, t_min, t_max)?;
+        let length = ray.direction().length();
+        let mut t = t_enter;
+        // A ray leaving the surface starts within epsilon of it; only accept
+        // a hit once the ray has been clear of the surface.
+        let mut clear = false;
+        for _ in 0..MAX_STEPS {
+            if t > t_exit {
+                return None;
+            }
+            let p = ray.at(t);
+            let d = self.sdf.distance(&p).abs();
+            if d < self.epsilon {
+                if clear {
+                    let outward_normal = self.normal(&p);
+                    let (u, v) = get_sphere_uv(&outward_normal);
+                    return Some(HitRecord::new(
+                        p,
+                        &self.material,
+                        t,
+                        u,
+                        v,
+                        ray,
+                        outward_normal,
+                    ));
+                }
+            } else {
+                clear = true;
+            }
+            t += (self.step_scale * d).max(self.epsilon) / length;
+        }
+        None
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(self.bbox.clone())
+    }
+}
@@ -0,0 +1,256 @@
+use crate::camera::Shutter;
+use crate::debug_view::DebugView;
+use crate::film::Filter;
+use std::env;
+
+// Light transport algorithm the image is rendered with.
+#[derive(Clone, Copy, PartialEq)]
+pub enum IntegratorKind {
+    Path,
+    // Bidirectional path tracing, for caustics and other light the camera
+    // subpath alone rarely finds.
+    Bdpt,
+    // Stochastic progressive photon mapping, for caustics seen through glass
+    // or in mirrors. Each sample per pixel is one photon pass.
+    Sppm,
+    // Primary-sample-space Metropolis light transport, for light that
+    // reaches the camera only through narrow paths. Each sample per pixel is
+    // one mutation per pixel on average.
+    Mlt,
+    // Ambient occlusion, for checking geometry quickly.
+    Ao,
+    // One bounce of light sampled from the lights list, for fast lookdev.
+    Direct,
+}
+
+impl IntegratorKind {
+    pub fn from_name(name: &str) -> IntegratorKind {
+        match name {
+            "path" => IntegratorKind::Path,
+            "bdpt" => IntegratorKind::Bdpt,
+            "sppm" => IntegratorKind::Sppm,
+            "mlt" => IntegratorKind::Mlt,
+            "ao" => IntegratorKind::Ao,
+            "direct" => IntegratorKind::Direct,
+            _ => panic!["unknown integrator {}", name],
+        }
+    }
+}
+
+// Render options, overridable from the command line.
+pub struct RenderSettings {
+    pub image_width: i32,
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+    pub output: String,
+    // Inclusive frame range; `None` renders a single still image.
+    pub frames: Option<(u32, u32)>,
+    pub fps: f64,
+    // Fraction of the frame interval the shutter stays open.
+    pub shutter: f64,
+    // Exposure at evenly spaced times from shutter open to close, linear in
+    // between; `None` exposes evenly.
+    pub shutter_curve: Option<Vec<f64>>,
+    // Check every light's sampling pdf before rendering.
+    pub validate_lights: bool,
+    // Also write albedo, normal, depth and other passes next to the image.
+    pub aovs: bool,
+    // Also write an `_denoised` copy of the image, filtered using the AOVs.
+    pub denoise: bool,
+    // Stop sampling a pixel once its relative standard error drops below
+    // this; `samples_per_pixel` becomes the cap. `None` samples every pixel
+    // fully.
+    pub adaptive_threshold: Option<f64>,
+    // Samples every pixel takes before adaptive sampling may stop it.
+    pub min_samples: i32,
+    // Also write an `_spp` image showing how many samples each pixel took.
+    pub heatmap: bool,
+    // Reconstruction filter samples are splatted with.
+    pub filter: Filter,
+    // Part of the image to render, (x0, x1, y0, y1) as fractions with y
+    // running down. Only that part is written out.
+    pub crop: (f64, f64, f64, f64),
+    // Limit per channel on light reaching the first hit after more than one
+    // bounce, to suppress fireflies. BDPT and SPPM can't tell which light
+    // that is, so they reject it.
+    pub clamp_indirect: Option<f64>,
+    // Also write an `_invalid` image marking pixels with NaN, infinite or
+    // negative samples.
+    pub debug_invalid: bool,
+    pub integrator: IntegratorKind,
+    // SPPM photons per pass; `None` shoots one per pixel.
+    pub photons: Option<usize>,
+    // SPPM starting gather radius; `None` picks one from the scene size.
+    pub photon_radius: Option<f64>,
+    // SPPM takes one diffuse bounce before gathering photons.
+    pub final_gather: bool,
+    // MLT paths traced to estimate image brightness and seed the chains.
+    pub bootstrap_samples: usize,
+    // Independent Markov chains MLT runs, in parallel.
+    pub chains: usize,
+    // Shade with a debug view instead of lighting, whatever the integrator.
+    pub debug_view: Option<DebugView>,
+    // Where the depth and heatmap debug views saturate; `None` picks a
+    // default for the view.
+    pub debug_scale: Option<f64>,
+    // How far ambient occlusion looks for occluders; `None` picks one from
+    // the scene size.
+    pub ao_distance: Option<f64>,
+    // Occlusion rays per camera sample.
+    pub ao_samples: u32,
+    // Lens prescription file to shoot still images through with a
+    // `RealisticCamera` instead of the pinhole camera.
+    pub lens: Option<String>,
+    // Diameter in millimetres to stop the lens down to; `None` leaves the
+    // prescription's own stop.
+    pub lens_aperture: Option<f64>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            image_width: 600,
+            samples_per_pixel: 1000,
+            max_depth: 50,
+            output: String::from("image.ppm"),
+            frames: None,
+            fps: 24.0,
+            shutter: 0.5,
+            shutter_curve: None,
+            validate_lights: false,
+            aovs: false,
+            denoise: false,
+            adaptive_threshold: None,
+            min_samples: 16,
+            heatmap: false,
+            filter: Filter::Box { radius: 0.5 },
+            crop: (0.0, 1.0, 0.0, 1.0),
+            clamp_indirect: None,
+            debug_invalid: false,
+            integrator: IntegratorKind::Path,
+            photons: None,
+            photon_radius: None,
+            final_gather: false,
+            bootstrap_samples: 100_000,
+            chains: 1000,
+            debug_view: None,
+            debug_scale: None,
+            ao_distance: None,
+            ao_samples: 16,
+            lens: None,
+            lens_aperture: None,
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, name: &str) -> T {
+    args.next()
+        .unwrap_or_else(|| panic!["missing value for {}", name])
+        .parse()
+        .unwrap_or_else(|_| panic!["invalid value for {}", name])
+}
+
+impl RenderSettings {
+    pub fn from_args() -> Self {
+        let mut settings = RenderSettings::default();
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--width" => settings.image_width = parse_value(&mut args, &arg),
+                "--spp" => settings.samples_per_pixel = parse_value(&mut args, &arg),
+                "--max-depth" => settings.max_depth = parse_value(&mut args, &arg),
+                "--output" => settings.output = parse_value(&mut args, &arg),
+                "--frames" => {
+                    let range: String = parse_value(&mut args, &arg);
+                    let (start, end) = range
+                        .split_once('-')
+                        .unwrap_or((range.as_str(), range.as_str()));
+                    settings.frames = Some((
+                        start.parse().expect("invalid start frame"),
+                        end.parse().expect("invalid end frame"),
+                    ));
+                }
+                "--fps" => settings.fps = parse_value(&mut args, &arg),
+                "--shutter" => settings.shutter = parse_value(&mut args, &arg),
+                "--shutter-curve" => {
+                    let weights: String = parse_value(&mut args, &arg);
+                    settings.shutter_curve = Some(
+                        weights
+                            .split(',')
+                            .map(|w| w.parse().expect("invalid shutter curve weight"))
+                            .collect(),
+                    );
+                }
+                "--validate-lights" => settings.validate_lights = true,
+                "--aovs" => settings.aovs = true,
+                "--denoise" => settings.denoise = true,
+                "--adaptive" => settings.adaptive_threshold = Some(parse_value(&mut args, &arg)),
+                "--min-spp" => settings.min_samples = parse_value(&mut args, &arg),
+                "--heatmap" => settings.heatmap = true,
+                "--filter" => {
+                    let name: String = parse_value(&mut args, &arg);
+                    settings.filter = Filter::from_name(&name);
+                }
+                "--filter-radius" => {
+                    settings.filter = settings.filter.with_radius(parse_value(&mut args, &arg))
+                }
+                "--clamp" => settings.clamp_indirect = Some(parse_value(&mut args, &arg)),
+                "--debug-invalid" => settings.debug_invalid = true,
+                "--integrator" => {
+                    let name: String = parse_value(&mut args, &arg);
+                    settings.integrator = IntegratorKind::from_name(&name);
+                }
+                "--photons" => settings.photons = Some(parse_value(&mut args, &arg)),
+                "--photon-radius" => settings.photon_radius = Some(parse_value(&mut args, &arg)),
+                "--final-gather" => settings.final_gather = true,
+                "--bootstrap" => settings.bootstrap_samples = parse_value(&mut args, &arg),
+                "--chains" => settings.chains = parse_value(&mut args, &arg),
+                "--debug-view" => {
+                    let name: String = parse_value(&mut args, &arg);
+                    settings.debug_view = Some(DebugView::from_name(&name));
+                }
+                "--debug-scale" => settings.debug_scale = Some(parse_value(&mut args, &arg)),
+                "--ao-distance" => settings.ao_distance = Some(parse_value(&mut args, &arg)),
+                "--ao-samples" => settings.ao_samples = parse_value(&mut args, &arg),
+                "--lens" => settings.lens = Some(parse_value(&mut args, &arg)),
+                "--lens-aperture" => settings.lens_aperture = Some(parse_value(&mut args, &arg)),
+                "--crop" => {
+                    let window: String = parse_value(&mut args, &arg);
+                    let v: Vec<f64> = window
+                        .split(',')
+                        .map(|x| x.parse().expect("invalid crop window"))
+                        .collect();
+                    if v.len() != 4 {
+                        panic!["--crop needs x0,x1,y0,y1"]
+                    }
+                    settings.crop = (v[0], v[1], v[2], v[3]);
+                }
+                _ => panic!["unknown option {}", arg],
+            }
+        }
+        if settings.clamp_indirect.is_some()
+            && matches!(
+                settings.integrator,
+                IntegratorKind::Bdpt | IntegratorKind::Sppm
+            )
+        {
+            panic!["--clamp isn't supported by --integrator bdpt or sppm"]
+        }
+        settings
+    }
+
+    // How exposure is spread over the shutter interval.
+    pub fn shutter(&self) -> Shutter {
+        self.shutter_curve
+            .as_deref()
+            .map_or(Shutter::Uniform, Shutter::curve)
+    }
+
+    // `image.ppm` becomes `image_0042.ppm` for frame 42.
+    pub fn frame_output(&self, frame: u32) -> String {
+        match self.output.rsplit_once('.') {
+            Some((stem, ext)) => format!("{}_{:04}.{}", stem, frame, ext),
+            None => format!("{}_{:04}", self.output, frame),
+        }
+    }
+}
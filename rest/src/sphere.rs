@@ -3,6 +3,7 @@ use crate::hittable::*;
 use crate::material::*;
 use crate::onb::*;
 use crate::ray::Ray;
+use crate::sampler;
 use crate::vec3::*;
 use rand::Rng;
 use std::f64;
@@ -15,8 +16,21 @@ pub fn get_sphere_uv(p: &Vec3) -> (f64, f64) {
     (u, v)
 }
 
+// Derivatives of the `get_sphere_uv` parameterisation at unit normal `n`.
+// Returns zero vectors at the poles, where dp/dv is undefined.
+pub fn get_sphere_tangents(n: &Vec3, radius: f64) -> (Vec3, Vec3) {
+    let rho = (n.x().powi(2) + n.z().powi(2)).sqrt();
+    if rho < 1e-8 {
+        return (Vec3::default(), Vec3::default());
+    }
+    let dpdu = 2.0 * f64::consts::PI * radius * Vec3::new(n.z(), 0.0, -n.x());
+    let dpdv =
+        f64::consts::PI * radius * Vec3::new(-n.y() * n.x() / rho, rho, -n.y() * n.z() / rho);
+    (dpdu, dpdv)
+}
+
 fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
-    let mut rng = rand::thread_rng();
+    let mut rng = sampler::rng();
     let r1 = rng.gen::<f64>();
     let r2 = rng.gen::<f64>();
     let z = 1.0 + r2 * ((1.0 - radius.powi(2) / distance_squared).sqrt() - 1.0);
@@ -67,15 +81,10 @@ impl<M: Material> Hittable for Sphere<M> {
         let p = r.at(t);
         let outward_normal = (&p - &self.center) / self.radius;
         let (u, v) = get_sphere_uv(&outward_normal);
-        Some(HitRecord::new(
-            p,
-            &self.material,
-            t,
-            u,
-            v,
-            r,
-            outward_normal,
-        ))
+        let (dpdu, dpdv) = get_sphere_tangents(&outward_normal, self.radius);
+        Some(
+            HitRecord::new(p, &self.material, t, u, v, r, outward_normal).with_tangents(dpdu, dpdv),
+        )
     }
 
     fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
@@ -0,0 +1,369 @@
+use crate::camera::CameraModel;
+use crate::hittable::*;
+use crate::light_sampler::*;
+use crate::material::*;
+use crate::pdf::PDF;
+use crate::ray::Ray;
+use crate::rtweekend::INFINITY;
+use crate::vec3::*;
+use rand::Rng;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+// Fraction of each iteration's new photons kept when shrinking the radius.
+const ALPHA: f64 = 2.0 / 3.0;
+
+// Where a pixel's camera path reached a diffuse surface this iteration.
+struct VisiblePoint<'a> {
+    rec: HitRecord<'a>,
+    r_in: Ray,
+    // Throughput from the camera.
+    beta: Color,
+}
+
+struct SppmPixel<'a> {
+    radius: f64,
+    // Light found by the camera paths themselves, summed over iterations.
+    ld: Color,
+    // Photon count and flux gathered so far, rescaled as the radius shrinks.
+    n: f64,
+    tau: Color,
+    vp: Option<VisiblePoint<'a>>,
+}
+
+// Hashed uniform grid over the visible points, each entered in every cell
+// its gather sphere overlaps.
+struct Grid {
+    min: Vec3,
+    cell: f64,
+    cells: Vec<Vec<usize>>,
+}
+
+impl Grid {
+    fn new(pixels: &[SppmPixel]) -> Self {
+        let mut min = Vec3::new(INFINITY, INFINITY, INFINITY);
+        let mut max_radius: f64 = 0.0;
+        for pixel in pixels {
+            if let Some(vp) = &pixel.vp {
+                for a in 0..3 {
+                    min[a] = min[a].min(vp.rec.p[a] - pixel.radius);
+                }
+                max_radius = max_radius.max(pixel.radius);
+            }
+        }
+        let mut grid = Grid {
+            min,
+            cell: 2.0 * max_radius.max(1e-6),
+            cells: vec![Vec::new(); pixels.len().max(1)],
+        };
+        for (i, pixel) in pixels.iter().enumerate() {
+            if let Some(vp) = &pixel.vp {
+                let r = Vec3::new(pixel.radius, pixel.radius, pixel.radius);
+                let lo = grid.cell_of(&(vp.rec.p - r));
+                let hi = grid.cell_of(&(vp.rec.p + r));
+                for x in lo.0..=hi.0 {
+                    for y in lo.1..=hi.1 {
+                        for z in lo.2..=hi.2 {
+                            let h = grid.hash((x, y, z));
+                            grid.cells[h].push(i);
+                        }
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    fn cell_of(&self, p: &Point3) -> (i64, i64, i64) {
+        let c = |a: usize| ((p[a] - self.min[a]) / self.cell).floor() as i64;
+        (c(0), c(1), c(2))
+    }
+
+    fn hash(&self, c: (i64, i64, i64)) -> usize {
+        let h = (c.0.wrapping_mul(73856093))
+            ^ (c.1.wrapping_mul(19349663))
+            ^ (c.2.wrapping_mul(83492791));
+        h.rem_euclid(self.cells.len() as i64) as usize
+    }
+
+    // Visible points that may lie within their radius of `p`.
+    fn candidates(&self, p: &Point3) -> &[usize] {
+        &self.cells[self.hash(self.cell_of(p))]
+    }
+}
+
+// Stochastic progressive photon mapping. Each iteration traces one camera
+// path per pixel through specular bounces to a visible point on a diffuse
+// surface, then shoots photons from the lights and gathers those landing
+// within each pixel's radius. The radius shrinks as photons accumulate, so
+// the estimate converges, and caustics seen through glass or in mirrors
+// come out where path tracing can't find them.
+//
+// Direct light at the visible point is sampled from the lights instead of
+// gathered. With `final_gather`, the camera path takes one more diffuse
+// bounce before stopping, so photon blotches are blurred away at the cost
+// of noise.
+pub struct Sppm<'a> {
+    pub world: &'a dyn Hittable,
+    pub lights: &'a dyn Hittable,
+    pub sampler: LightSampler<'a>,
+    pub camera: &'a dyn CameraModel,
+    pub background: Color,
+    pub max_depth: i32,
+    pub photons_per_iteration: usize,
+    pub initial_radius: f64,
+    pub final_gather: bool,
+    // Image size and crop window [x0, x1) by [y0, y1), y running down.
+    pub image_width: usize,
+    pub image_height: usize,
+    pub crop: (usize, usize, usize, usize),
+}
+
+impl<'a> Sppm<'a> {
+    // Radiance of every pixel of the crop window, rows from top to bottom.
+    pub fn render(&self, iterations: i32) -> Vec<Color> {
+        let (x0, x1, y0, y1) = self.crop;
+        let width = x1 - x0;
+        let mut pixels: Vec<SppmPixel<'a>> = (0..width * (y1 - y0))
+            .map(|_| SppmPixel {
+                radius: self.initial_radius,
+                ld: Color::default(),
+                n: 0.0,
+                tau: Color::default(),
+                vp: None,
+            })
+            .collect();
+        for iteration in 1..=iterations {
+            pixels.par_iter_mut().enumerate().for_each(|(k, pixel)| {
+                let mut rng = rand::thread_rng();
+                let i = x0 + k % width;
+                // Camera rows count up from the bottom of the image.
+                let j = self.image_height - 1 - (y0 + k / width);
+                let u = (i as f64 + rng.gen::<f64>()) / (self.image_width as f64 - 1.0);
+                let v = (j as f64 + rng.gen::<f64>()) / (self.image_height as f64 - 1.0);
+                let (r, weight) = self.camera.get_weighted_ray(u, v);
+                pixel.vp = None;
+                if weight > 0.0 {
+                    let (ld, vp) = self.visible_point(r, weight);
+                    pixel.ld += ld;
+                    pixel.vp = vp;
+                }
+            });
+
+            let grid = Grid::new(&pixels);
+            let photons = (0..self.photons_per_iteration)
+                .into_par_iter()
+                .fold(
+                    || vec![(Color::default(), 0u64); pixels.len()],
+                    |mut acc, _| {
+                        self.trace_photon(&grid, &pixels, &mut acc);
+                        acc
+                    },
+                )
+                .reduce(
+                    || vec![(Color::default(), 0u64); pixels.len()],
+                    |mut a, b| {
+                        for (x, y) in a.iter_mut().zip(b) {
+                            x.0 += y.0;
+                            x.1 += y.1;
+                        }
+                        a
+                    },
+                );
+
+            for (pixel, (phi, m)) in pixels.iter_mut().zip(photons) {
+                if m == 0 {
+                    continue;
+                }
+                let beta = pixel.vp.as_ref().map_or(Color::default(), |vp| vp.beta);
+                let m = m as f64;
+                let n = pixel.n + ALPHA * m;
+                let radius = pixel.radius * (n / (pixel.n + m)).sqrt();
+                let shrink = (radius / pixel.radius).powi(2);
+                pixel.tau = (pixel.tau + &beta * &phi) * shrink;
+                pixel.n = n;
+                pixel.radius = radius;
+            }
+
+            eprint!("\rIterations remaining: {} ", iterations - iteration);
+        }
+
+        let photons = iterations as f64 * self.photons_per_iteration as f64;
+        pixels
+            .iter()
+            .map(|p| p.ld / iterations as f64 + p.tau / (photons * PI * p.radius * p.radius))
+            .collect()
+    }
+
+    // Follows the camera ray `r` to the visible point, returning the light
+    // found along the way.
+    fn visible_point(&self, r: Ray, weight: f64) -> (Color, Option<VisiblePoint<'a>>) {
+        let mut ray = r;
+        let mut beta = Color::new(weight, weight, weight);
+        let mut ld = Color::default();
+        // After the final gather bounce, only light reached through specular
+        // bounces is new; the rest was sampled as direct light already.
+        let mut gathering = false;
+        let mut specular = false;
+        for _ in 0..self.max_depth {
+            let counts = !gathering || specular;
+            let rec = match self.world.hit(&ray, 0.001, INFINITY) {
+                Some(rec) => rec,
+                None => {
+                    if counts {
+                        ld += &beta * &self.background;
+                    }
+                    return (ld, None);
+                }
+            };
+            if counts {
+                ld += &beta * &rec.material.emitted(&ray, &rec);
+            }
+            match rec.material.scatter(&ray, &rec) {
+                None => return (ld, None),
+                Some(ScatterRecord::Specular {
+                    specular_ray,
+                    attenuation,
+                }) => {
+                    beta = &beta * &attenuation;
+                    ray = specular_ray;
+                    specular |= gathering;
+                }
+                Some(ScatterRecord::Scatter { pdf, attenuation }) => {
+                    if gathering || !self.final_gather {
+                        if !gathering {
+                            ld += &beta * &self.direct(&ray, &rec, &pdf, &attenuation);
+                        }
+                        return (
+                            ld,
+                            Some(VisiblePoint {
+                                rec,
+                                r_in: ray,
+                                beta,
+                            }),
+                        );
+                    }
+                    ld += &beta * &self.direct(&ray, &rec, &pdf, &attenuation);
+                    let dir = pdf.generate();
+                    let pdf_dir = pdf.value(&dir);
+                    if pdf_dir <= 0.0 {
+                        return (ld, None);
+                    }
+                    let scattered = Ray::new(rec.p, dir, ray.time());
+                    let f_cos = attenuation * rec.material.scattering_pdf(&ray, &rec, &scattered);
+                    beta = &beta * &(f_cos / pdf_dir);
+                    ray = scattered;
+                    gathering = true;
+                }
+            }
+        }
+        (ld, None)
+    }
+
+    // One-sample estimate of light arriving at `rec` straight from an
+    // emitter, mixing light and material sampling like the path tracer.
+    fn direct(&self, r: &Ray, rec: &HitRecord, pdf: &PDF, attenuation: &Color) -> Color {
+        let light_pdf = PDF::hittable(self.lights, &rec.p);
+        let mixture = PDF::mixture(&light_pdf, pdf);
+        let dir = mixture.generate();
+        let pdf_dir = mixture.value(&dir);
+        if pdf_dir <= 0.0 {
+            return Color::default();
+        }
+        let scattered = Ray::new(rec.p, dir, r.time());
+        let f_cos = *attenuation * (rec.material.scattering_pdf(r, rec, &scattered) / pdf_dir);
+        let le = match self.world.hit(&scattered, 0.001, INFINITY) {
+            Some(hit) => hit.material.emitted(&scattered, &hit),
+            None => self.background,
+        };
+        &f_cos * &le
+    }
+
+    // Shoots one photon, adding its flux times the material at every visible
+    // point it lands near to `acc`, along with a count.
+    fn trace_photon(&self, grid: &Grid, pixels: &[SppmPixel], acc: &mut [(Color, u64)]) {
+        let time = self.camera.sample_time();
+        let point = match self.sampler.sample(time) {
+            Some(point) => point,
+            None => return,
+        };
+        let (mut ray, mut beta, _) = match self.sampler.sample_ray(&point, time) {
+            Some(sample) => sample,
+            None => return,
+        };
+        let mut rng = rand::thread_rng();
+        for depth in 0..self.max_depth {
+            let rec = match self.world.hit(&ray, 0.001, INFINITY) {
+                Some(rec) => rec,
+                None => return,
+            };
+            let next = match rec.material.scatter(&ray, &rec) {
+                None => return,
+                Some(ScatterRecord::Specular {
+                    specular_ray,
+                    attenuation,
+                }) => {
+                    ray = specular_ray;
+                    &beta * &attenuation
+                }
+                Some(ScatterRecord::Scatter { pdf, attenuation }) => {
+                    // The first hit is direct light, which visible points
+                    // sample themselves unless they are gathered from.
+                    if depth > 0 || self.final_gather {
+                        let wi = -unit_vector(ray.direction());
+                        for &k in grid.candidates(&rec.p) {
+                            let vp = match &pixels[k].vp {
+                                Some(vp) => vp,
+                                None => continue,
+                            };
+                            let r = pixels[k].radius;
+                            if (vp.rec.p - rec.p).length_squared() > r * r {
+                                continue;
+                            }
+                            let f = self.f(vp, &wi);
+                            acc[k].0 += &beta * &f;
+                            acc[k].1 += 1;
+                        }
+                    }
+                    let dir = pdf.generate();
+                    let pdf_dir = pdf.value(&dir);
+                    if pdf_dir <= 0.0 {
+                        return;
+                    }
+                    let scattered = Ray::new(rec.p, dir, time);
+                    let f_cos = attenuation * rec.material.scattering_pdf(&ray, &rec, &scattered);
+                    ray = scattered;
+                    &beta * &(f_cos / pdf_dir)
+                }
+            };
+            // Russian roulette on how much the bounce kept.
+            let old = beta.x().max(beta.y()).max(beta.z());
+            let q = if old > 0.0 {
+                (next.x().max(next.y()).max(next.z()) / old).min(1.0)
+            } else {
+                0.0
+            };
+            if q <= 0.0 || rng.gen::<f64>() >= q {
+                return;
+            }
+            beta = next / q;
+        }
+    }
+
+    // Material at a visible point for light arriving from `wi`, without the
+    // cosine, which the photon density already accounts for.
+    fn f(&self, vp: &VisiblePoint, wi: &Vec3) -> Color {
+        let rec = &vp.rec;
+        let cosine = dot(&rec.material.shading_normal(rec), wi).abs();
+        if cosine < 1e-8 {
+            return Color::default();
+        }
+        match rec.material.scatter(&vp.r_in, rec) {
+            Some(ScatterRecord::Scatter { attenuation, .. }) => {
+                let scattered = Ray::new(rec.p, *wi, vp.r_in.time());
+                attenuation * (rec.material.scattering_pdf(&vp.r_in, rec, &scattered) / cosine)
+            }
+            _ => Color::default(),
+        }
+    }
+}
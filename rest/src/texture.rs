@@ -1,8 +1,17 @@
 use crate::perlin::Perlin;
+use crate::rotate::Axis;
 use crate::vec3::*;
+use crate::worley::Worley;
+use std::f64;
 
 pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3;
+
+    // Lookup that also knows the surface normal. Only projections such as
+    // `TriplanarTexture` need it; wrappers forward it to their inputs.
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, _n: &Vec3) -> Vec3 {
+        self.value(u, v, p)
+    }
 }
 
 #[derive(Clone)]
@@ -34,26 +43,87 @@ impl Texture for SolidColor {
 pub struct CheckerTexture<T: Texture, U: Texture> {
     odd: T,
     even: U,
+    scale: f64,
 }
 
 impl<T: Texture, U: Texture> CheckerTexture<T, U> {
     #[allow(dead_code)]
     pub fn new(odd: T, even: U) -> Self {
-        CheckerTexture { odd, even }
+        CheckerTexture::with_scale(odd, even, 10.0)
+    }
+
+    #[allow(dead_code)]
+    pub fn with_scale(odd: T, even: U, scale: f64) -> Self {
+        CheckerTexture { odd, even, scale }
+    }
+}
+
+impl<T: Texture, U: Texture> CheckerTexture<T, U> {
+    fn select(&self, p: &Vec3) -> &dyn Texture {
+        let sines = f64::sin(self.scale * p.x())
+            * f64::sin(self.scale * p.y())
+            * f64::sin(self.scale * p.z());
+        if sines < 0.0 {
+            &self.odd
+        } else {
+            &self.even
+        }
     }
 }
 
 impl<T: Texture, U: Texture> Texture for CheckerTexture<T, U> {
     fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
-        let sines = f64::sin(10.0 * p.x()) * f64::sin(10.0 * p.y()) * f64::sin(10.0 * p.z());
-        if sines < 0.0 {
-            self.odd.value(u, v, p)
+        self.select(p).value(u, v, p)
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.select(p).value_with_normal(u, v, p, n)
+    }
+}
+
+// Checker laid out in texture space: `scale_u` x `scale_v` squares over the unit UV square.
+#[derive(Clone)]
+pub struct UVCheckerTexture<T: Texture, U: Texture> {
+    odd: T,
+    even: U,
+    scale_u: f64,
+    scale_v: f64,
+}
+
+impl<T: Texture, U: Texture> UVCheckerTexture<T, U> {
+    #[allow(dead_code)]
+    pub fn new(odd: T, even: U, scale_u: f64, scale_v: f64) -> Self {
+        UVCheckerTexture {
+            odd,
+            even,
+            scale_u,
+            scale_v,
+        }
+    }
+}
+
+impl<T: Texture, U: Texture> UVCheckerTexture<T, U> {
+    fn select(&self, u: f64, v: f64) -> &dyn Texture {
+        let iu = f64::floor(u * self.scale_u) as i64;
+        let iv = f64::floor(v * self.scale_v) as i64;
+        if (iu + iv) % 2 != 0 {
+            &self.odd
         } else {
-            self.even.value(u, v, p)
+            &self.even
         }
     }
 }
 
+impl<T: Texture, U: Texture> Texture for UVCheckerTexture<T, U> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.select(u, v).value(u, v, p)
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.select(u, v).value_with_normal(u, v, p, n)
+    }
+}
+
 #[derive(Clone)]
 pub struct NoiseTexture {
     noise: Perlin,
@@ -77,6 +147,238 @@ impl Texture for NoiseTexture {
     }
 }
 
+#[derive(Clone)]
+pub struct FbmTexture {
+    noise: Perlin,
+    scale: f64,
+    octaves: usize,
+    lacunarity: f64,
+    gain: f64,
+}
+
+impl FbmTexture {
+    #[allow(dead_code)]
+    pub fn new(scale: f64, octaves: usize, lacunarity: f64, gain: f64) -> Self {
+        FbmTexture {
+            noise: Perlin::new(),
+            scale,
+            octaves,
+            lacunarity,
+            gain,
+        }
+    }
+}
+
+impl Texture for FbmTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Vec3 {
+        let n = self
+            .noise
+            .fbm(&(self.scale * p), self.octaves, self.lacunarity, self.gain);
+        let g = (0.5 * (1.0 + n)).clamp(0.0, 1.0);
+        Vec3::new(g, g, g)
+    }
+}
+
+#[derive(Clone)]
+pub struct RidgedTexture {
+    noise: Perlin,
+    scale: f64,
+    octaves: usize,
+    lacunarity: f64,
+    gain: f64,
+}
+
+impl RidgedTexture {
+    #[allow(dead_code)]
+    pub fn new(scale: f64, octaves: usize, lacunarity: f64, gain: f64) -> Self {
+        RidgedTexture {
+            noise: Perlin::new(),
+            scale,
+            octaves,
+            lacunarity,
+            gain,
+        }
+    }
+}
+
+impl Texture for RidgedTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Vec3 {
+        let g = self
+            .noise
+            .ridged(&(self.scale * p), self.octaves, self.lacunarity, self.gain);
+        Vec3::new(g, g, g)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum WorleyFeature {
+    F1,
+    F2,
+    F2MinusF1,
+}
+
+#[derive(Clone)]
+pub struct WorleyTexture {
+    noise: Worley,
+    scale: f64,
+    feature: WorleyFeature,
+}
+
+impl WorleyTexture {
+    #[allow(dead_code)]
+    pub fn new(scale: f64, feature: WorleyFeature) -> Self {
+        WorleyTexture {
+            noise: Worley::new(),
+            scale,
+            feature,
+        }
+    }
+}
+
+impl Texture for WorleyTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Vec3 {
+        let (f1, f2) = self.noise.distances(&(self.scale * p));
+        let g = match self.feature {
+            WorleyFeature::F1 => f1,
+            WorleyFeature::F2 => f2,
+            WorleyFeature::F2MinusF1 => f2 - f1,
+        }
+        .clamp(0.0, 1.0);
+        Vec3::new(g, g, g)
+    }
+}
+
+#[derive(Clone)]
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,
+    turbulence: f64,
+    base: Color,
+    vein: Color,
+}
+
+impl MarbleTexture {
+    #[allow(dead_code)]
+    pub fn new(scale: f64) -> Self {
+        MarbleTexture::with_colors(
+            scale,
+            10.0,
+            Color::new(0.9, 0.9, 0.88),
+            Color::new(0.25, 0.25, 0.3),
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn with_colors(scale: f64, turbulence: f64, base: Color, vein: Color) -> Self {
+        MarbleTexture {
+            noise: Perlin::new(),
+            scale,
+            turbulence,
+            base,
+            vein,
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Vec3 {
+        let t =
+            0.5 * (1.0 + f64::sin(self.scale * p.x() + self.turbulence * self.noise.turb(p, 7)));
+        lerp(&self.vein, &self.base, t)
+    }
+}
+
+// Concentric rings around the y axis, perturbed by turbulence.
+#[derive(Clone)]
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64,
+    rings: f64,
+    light: Color,
+    dark: Color,
+}
+
+impl WoodTexture {
+    #[allow(dead_code)]
+    pub fn new(scale: f64) -> Self {
+        WoodTexture::with_colors(
+            scale,
+            12.0,
+            Color::new(0.75, 0.55, 0.3),
+            Color::new(0.4, 0.22, 0.08),
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn with_colors(scale: f64, rings: f64, light: Color, dark: Color) -> Self {
+        WoodTexture {
+            noise: Perlin::new(),
+            scale,
+            rings,
+            light,
+            dark,
+        }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Vec3 {
+        let sp = self.scale * p;
+        let radius = f64::sqrt(sp.x() * sp.x() + sp.z() * sp.z());
+        let g = self.rings * (radius + 0.2 * self.noise.turb(&sp, 4));
+        let t = g - g.floor();
+        lerp(&self.light, &self.dark, t)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum GradientAxis {
+    U,
+    V,
+    X,
+    Y,
+    Z,
+}
+
+// Linear ramp between two colors along a texture or world axis, clamped outside [start, end].
+#[derive(Clone)]
+pub struct GradientTexture {
+    axis: GradientAxis,
+    start: f64,
+    end: f64,
+    from: Color,
+    to: Color,
+}
+
+impl GradientTexture {
+    #[allow(dead_code)]
+    pub fn new(axis: GradientAxis, start: f64, end: f64, from: Color, to: Color) -> Self {
+        GradientTexture {
+            axis,
+            start,
+            end,
+            from,
+            to,
+        }
+    }
+}
+
+impl Texture for GradientTexture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let x = match self.axis {
+            GradientAxis::U => u,
+            GradientAxis::V => v,
+            GradientAxis::X => p.x(),
+            GradientAxis::Y => p.y(),
+            GradientAxis::Z => p.z(),
+        };
+        let t = ((x - self.start) / (self.end - self.start)).clamp(0.0, 1.0);
+        lerp(&self.from, &self.to, t)
+    }
+}
+
 #[derive(Clone)]
 pub struct ImageTexture {
     data: Vec<u8>,
@@ -110,3 +412,126 @@ impl Texture for ImageTexture {
         Vec3::new(r, g, b)
     }
 }
+
+// Texture-space transforms. Each wrapper maps the lookup coordinates before
+// delegating, so they compose by nesting, e.g.
+// `OffsetTexture::new(ScaleTexture::new(t, s), o)` scales then offsets.
+// The x and y components of vector parameters also apply to u and v.
+
+#[derive(Clone)]
+pub struct ScaleTexture<T: Texture> {
+    texture: T,
+    scale: Vec3,
+}
+
+impl<T: Texture> ScaleTexture<T> {
+    #[allow(dead_code)]
+    pub fn new(texture: T, scale: Vec3) -> Self {
+        ScaleTexture { texture, scale }
+    }
+}
+
+impl<T: Texture> Texture for ScaleTexture<T> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.texture
+            .value(u * self.scale.x(), v * self.scale.y(), &(p * &self.scale))
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.texture.value_with_normal(
+            u * self.scale.x(),
+            v * self.scale.y(),
+            &(p * &self.scale),
+            n,
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct OffsetTexture<T: Texture> {
+    texture: T,
+    offset: Vec3,
+}
+
+impl<T: Texture> OffsetTexture<T> {
+    #[allow(dead_code)]
+    pub fn new(texture: T, offset: Vec3) -> Self {
+        OffsetTexture { texture, offset }
+    }
+}
+
+impl<T: Texture> Texture for OffsetTexture<T> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.texture.value(
+            u + self.offset.x(),
+            v + self.offset.y(),
+            &(p + &self.offset),
+        )
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.texture.value_with_normal(
+            u + self.offset.x(),
+            v + self.offset.y(),
+            &(p + &self.offset),
+            n,
+        )
+    }
+}
+
+// Rotates the lookup point about a world axis and the UV coordinates about (0.5, 0.5).
+#[derive(Clone)]
+pub struct RotateTexture<T: Texture> {
+    texture: T,
+    axis: Axis,
+    sin_theta: f64,
+    cos_theta: f64,
+}
+
+impl<T: Texture> RotateTexture<T> {
+    #[allow(dead_code)]
+    pub fn new(texture: T, axis: Axis, angle: f64) -> Self {
+        let radians = (f64::consts::PI / 180.0) * angle;
+        RotateTexture {
+            texture,
+            axis,
+            sin_theta: f64::sin(radians),
+            cos_theta: f64::cos(radians),
+        }
+    }
+}
+
+impl<T: Texture> RotateTexture<T> {
+    fn rotate(&self, p: &Vec3) -> Vec3 {
+        let (a_axis, b_axis) = match self.axis {
+            Axis::X => (1, 2),
+            Axis::Y => (2, 0),
+            Axis::Z => (0, 1),
+        };
+        let mut rotated = *p;
+        rotated[a_axis] = self.cos_theta * p[a_axis] - self.sin_theta * p[b_axis];
+        rotated[b_axis] = self.sin_theta * p[a_axis] + self.cos_theta * p[b_axis];
+        rotated
+    }
+
+    fn rotate_uv(&self, u: f64, v: f64) -> (f64, f64) {
+        let (du, dv) = (u - 0.5, v - 0.5);
+        (
+            0.5 + self.cos_theta * du - self.sin_theta * dv,
+            0.5 + self.sin_theta * du + self.cos_theta * dv,
+        )
+    }
+}
+
+impl<T: Texture> Texture for RotateTexture<T> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let (u, v) = self.rotate_uv(u, v);
+        self.texture.value(u, v, &self.rotate(p))
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        let (u, v) = self.rotate_uv(u, v);
+        self.texture
+            .value_with_normal(u, v, &self.rotate(p), &self.rotate(n))
+    }
+}
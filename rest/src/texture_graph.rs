@@ -0,0 +1,288 @@
+use crate::color::luminance;
+use crate::texture::Texture;
+use crate::vec3::*;
+
+// Combinator textures. Each node evaluates its inputs through a closure so the
+// same code serves both `value` and `value_with_normal`. Where a node needs a
+// scalar (mix factor, ramp position) it takes the luminance of its input.
+
+#[derive(Clone)]
+pub struct MixTexture<A: Texture, B: Texture, F: Texture> {
+    a: A,
+    b: B,
+    factor: F,
+}
+
+impl<A: Texture, B: Texture, F: Texture> MixTexture<A, B, F> {
+    #[allow(dead_code)]
+    pub fn new(a: A, b: B, factor: F) -> Self {
+        MixTexture { a, b, factor }
+    }
+
+    fn eval(&self, f: impl Fn(&dyn Texture) -> Vec3) -> Vec3 {
+        let t = luminance(&f(&self.factor)).clamp(0.0, 1.0);
+        lerp(&f(&self.a), &f(&self.b), t)
+    }
+}
+
+impl<A: Texture, B: Texture, F: Texture> Texture for MixTexture<A, B, F> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.eval(|t| t.value(u, v, p))
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.eval(|t| t.value_with_normal(u, v, p, n))
+    }
+}
+
+#[derive(Clone)]
+pub struct AddTexture<A: Texture, B: Texture> {
+    a: A,
+    b: B,
+}
+
+impl<A: Texture, B: Texture> AddTexture<A, B> {
+    #[allow(dead_code)]
+    pub fn new(a: A, b: B) -> Self {
+        AddTexture { a, b }
+    }
+
+    fn eval(&self, f: impl Fn(&dyn Texture) -> Vec3) -> Vec3 {
+        f(&self.a) + f(&self.b)
+    }
+}
+
+impl<A: Texture, B: Texture> Texture for AddTexture<A, B> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.eval(|t| t.value(u, v, p))
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.eval(|t| t.value_with_normal(u, v, p, n))
+    }
+}
+
+#[derive(Clone)]
+pub struct MultiplyTexture<A: Texture, B: Texture> {
+    a: A,
+    b: B,
+}
+
+impl<A: Texture, B: Texture> MultiplyTexture<A, B> {
+    #[allow(dead_code)]
+    pub fn new(a: A, b: B) -> Self {
+        MultiplyTexture { a, b }
+    }
+
+    fn eval(&self, f: impl Fn(&dyn Texture) -> Vec3) -> Vec3 {
+        &f(&self.a) * &f(&self.b)
+    }
+}
+
+impl<A: Texture, B: Texture> Texture for MultiplyTexture<A, B> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.eval(|t| t.value(u, v, p))
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.eval(|t| t.value_with_normal(u, v, p, n))
+    }
+}
+
+#[derive(Clone)]
+pub struct InvertTexture<T: Texture> {
+    texture: T,
+}
+
+impl<T: Texture> InvertTexture<T> {
+    #[allow(dead_code)]
+    pub fn new(texture: T) -> Self {
+        InvertTexture { texture }
+    }
+
+    fn eval(&self, f: impl Fn(&dyn Texture) -> Vec3) -> Vec3 {
+        Vec3::new(1.0, 1.0, 1.0) - f(&self.texture)
+    }
+}
+
+impl<T: Texture> Texture for InvertTexture<T> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.eval(|t| t.value(u, v, p))
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.eval(|t| t.value_with_normal(u, v, p, n))
+    }
+}
+
+#[derive(Clone)]
+pub struct ClampTexture<T: Texture> {
+    texture: T,
+    min: f64,
+    max: f64,
+}
+
+impl<T: Texture> ClampTexture<T> {
+    #[allow(dead_code)]
+    pub fn new(texture: T, min: f64, max: f64) -> Self {
+        ClampTexture { texture, min, max }
+    }
+
+    fn eval(&self, f: impl Fn(&dyn Texture) -> Vec3) -> Vec3 {
+        let c = f(&self.texture);
+        Vec3::new(
+            c.x().clamp(self.min, self.max),
+            c.y().clamp(self.min, self.max),
+            c.z().clamp(self.min, self.max),
+        )
+    }
+}
+
+impl<T: Texture> Texture for ClampTexture<T> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.eval(|t| t.value(u, v, p))
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.eval(|t| t.value_with_normal(u, v, p, n))
+    }
+}
+
+// Linearly maps each channel from [in_min, in_max] to [out_min, out_max], without clamping.
+#[derive(Clone)]
+pub struct RemapTexture<T: Texture> {
+    texture: T,
+    in_min: f64,
+    in_max: f64,
+    out_min: f64,
+    out_max: f64,
+}
+
+impl<T: Texture> RemapTexture<T> {
+    #[allow(dead_code)]
+    pub fn new(texture: T, in_min: f64, in_max: f64, out_min: f64, out_max: f64) -> Self {
+        RemapTexture {
+            texture,
+            in_min,
+            in_max,
+            out_min,
+            out_max,
+        }
+    }
+
+    fn remap(&self, x: f64) -> f64 {
+        let t = (x - self.in_min) / (self.in_max - self.in_min);
+        self.out_min + t * (self.out_max - self.out_min)
+    }
+
+    fn eval(&self, f: impl Fn(&dyn Texture) -> Vec3) -> Vec3 {
+        let c = f(&self.texture);
+        Vec3::new(self.remap(c.x()), self.remap(c.y()), self.remap(c.z()))
+    }
+}
+
+impl<T: Texture> Texture for RemapTexture<T> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.eval(|t| t.value(u, v, p))
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.eval(|t| t.value_with_normal(u, v, p, n))
+    }
+}
+
+// Maps the input's luminance through piecewise-linear color keys.
+#[derive(Clone)]
+pub struct ColorRampTexture<T: Texture> {
+    input: T,
+    keys: Vec<(f64, Color)>,
+}
+
+impl<T: Texture> ColorRampTexture<T> {
+    #[allow(dead_code)]
+    pub fn new(input: T, mut keys: Vec<(f64, Color)>) -> Self {
+        if keys.is_empty() {
+            panic!["color ramp needs at least one key"]
+        }
+        keys.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        ColorRampTexture { input, keys }
+    }
+
+    fn ramp(&self, x: f64) -> Color {
+        let first = &self.keys[0];
+        if x <= first.0 {
+            return first.1;
+        }
+        for pair in self.keys.windows(2) {
+            let (x0, c0) = &pair[0];
+            let (x1, c1) = &pair[1];
+            if x <= *x1 {
+                let t = if x1 > x0 { (x - x0) / (x1 - x0) } else { 1.0 };
+                return lerp(c0, c1, t);
+            }
+        }
+        self.keys[self.keys.len() - 1].1
+    }
+
+    fn eval(&self, f: impl Fn(&dyn Texture) -> Vec3) -> Vec3 {
+        self.ramp(luminance(&f(&self.input)))
+    }
+}
+
+impl<T: Texture> Texture for ColorRampTexture<T> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.eval(|t| t.value(u, v, p))
+    }
+
+    fn value_with_normal(&self, u: f64, v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.eval(|t| t.value_with_normal(u, v, p, n))
+    }
+}
+
+// Projects a UV texture along the three world axes and blends the projections
+// by the surface normal, raised to `sharpness`. Without a usable normal the
+// three projections are weighted equally.
+#[derive(Clone)]
+pub struct TriplanarTexture<T: Texture> {
+    texture: T,
+    scale: f64,
+    sharpness: f64,
+}
+
+impl<T: Texture> TriplanarTexture<T> {
+    #[allow(dead_code)]
+    pub fn new(texture: T, scale: f64, sharpness: f64) -> Self {
+        TriplanarTexture {
+            texture,
+            scale,
+            sharpness,
+        }
+    }
+
+    fn project(&self, p: &Vec3, n: &Vec3) -> Vec3 {
+        let mut wx = n.x().abs().powf(self.sharpness);
+        let mut wy = n.y().abs().powf(self.sharpness);
+        let mut wz = n.z().abs().powf(self.sharpness);
+        // A zero or broken normal, such as at the pole of a disk, picks no
+        // axis, so blend them equally rather than dividing by zero.
+        let mut sum = wx + wy + wz;
+        if sum.is_nan() || sum < 1e-12 {
+            (wx, wy, wz, sum) = (1.0, 1.0, 1.0, 3.0);
+        }
+        let sp = self.scale * p;
+        let x = self.texture.value_with_normal(sp.y(), sp.z(), p, n);
+        let y = self.texture.value_with_normal(sp.z(), sp.x(), p, n);
+        let z = self.texture.value_with_normal(sp.x(), sp.y(), p, n);
+        (wx * x + wy * y + wz * z) / sum
+    }
+}
+
+impl<T: Texture> Texture for TriplanarTexture<T> {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Vec3 {
+        self.project(p, &Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    fn value_with_normal(&self, _u: f64, _v: f64, p: &Vec3, n: &Vec3) -> Vec3 {
+        self.project(p, n)
+    }
+}
@@ -0,0 +1,128 @@
+use crate::aabb::AABB;
+use crate::hittable::*;
+use crate::material::Material;
+use crate::pdf::{surface_pdf_value, SURFACE_PDF_TOLERANCE};
+use crate::poly::{polish_root, solve_quartic};
+use crate::ray::Ray;
+use crate::sampler;
+use crate::sphere::get_sphere_uv;
+use crate::vec3::*;
+use rand::Rng;
+use std::f64;
+
+// Torus lying in the xz plane around `center`: a tube of `minor_radius` swept
+// around a circle of `major_radius`.
+#[derive(Clone)]
+pub struct Torus<M: Material> {
+    center: Point3,
+    major_radius: f64,
+    minor_radius: f64,
+    material: M,
+}
+
+impl<M: Material> Torus<M> {
+    #[allow(dead_code)]
+    pub fn new(center: Point3, major_radius: f64, minor_radius: f64, material: M) -> Self {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl<M: Material> Hittable for Torus<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if !self.bounding_box(0.0, 0.0)?.hit(ray, t_min, t_max) {
+            return None;
+        }
+
+        // Solve in units of the major radius, along a unit direction from the
+        // point on the ray closest to the center, which keeps the quartic
+        // well conditioned.
+        let scale = self.major_radius;
+        let length = ray.direction().length();
+        let d = ray.direction() / length;
+        let o = (ray.origin() - &self.center) / scale;
+        let t0 = -dot(&o, &d);
+        let o = o + t0 * d;
+
+        let r = self.minor_radius / scale;
+        let e = o.length_squared() - 1.0 - r * r;
+        let f = dot(&o, &d);
+        let coeffs = [
+            e * e - 4.0 * (r * r - o.y() * o.y()),
+            4.0 * f * e + 8.0 * o.y() * d.y(),
+            2.0 * e + 4.0 * f * f + 4.0 * d.y() * d.y(),
+            4.0 * f,
+            1.0,
+        ];
+        let t = solve_quartic(coeffs)
+            .into_iter()
+            .map(|s| scale * (t0 + polish_root(&coeffs, s)) / length)
+            .filter(|&t| t >= t_min && t <= t_max)
+            .fold(None, |best: Option<f64>, t| {
+                Some(best.map_or(t, |b| b.min(t)))
+            })?;
+
+        let p = ray.at(t);
+        let local = p - self.center;
+        let radial = Vec3::new(local.x(), 0.0, local.z());
+        let rho = radial.length();
+        let ring = (self.major_radius / rho) * radial;
+        let outward_normal = (local - ring) / self.minor_radius;
+        // u goes around the ring as in `get_sphere_uv`, v around the tube.
+        let (u, _) = get_sphere_uv(&radial);
+        let theta = local.y().atan2(rho - self.major_radius);
+        let v = (theta + f64::consts::PI) / (2.0 * f64::consts::PI);
+        let dpdu = 2.0 * f64::consts::PI * Vec3::new(local.z(), 0.0, -local.x());
+        let dpdv = 2.0
+            * f64::consts::PI
+            * Vec3::new(
+                -local.y() * local.x() / rho,
+                rho - self.major_radius,
+                -local.y() * local.z() / rho,
+            );
+        Some(
+            HitRecord::new(p, &self.material, t, u, v, ray, outward_normal)
+                .with_tangents(dpdu, dpdv),
+        )
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = Vec3::new(outer, self.minor_radius, outer);
+        Some(AABB::new(self.center - extent, self.center + extent))
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        let area = 4.0 * f64::consts::PI.powi(2) * self.major_radius * self.minor_radius;
+        surface_pdf_value(self, o, v, area)
+    }
+
+    fn sampling_tolerance(&self) -> f64 {
+        SURFACE_PDF_TOLERANCE
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        let mut rng = sampler::rng();
+        // The outside of the tube has more area than the inside, so accept
+        // tube angles in proportion to their distance from the axis.
+        let theta = loop {
+            let theta = 2.0 * f64::consts::PI * rng.gen::<f64>();
+            let reach = self.major_radius + self.minor_radius * theta.cos();
+            if rng.gen::<f64>() * (self.major_radius + self.minor_radius) <= reach {
+                break theta;
+            }
+        };
+        let phi = 2.0 * f64::consts::PI * rng.gen::<f64>();
+        let rho = self.major_radius + self.minor_radius * theta.cos();
+        let p = Vec3::new(
+            rho * phi.cos(),
+            self.minor_radius * theta.sin(),
+            rho * phi.sin(),
+        );
+        self.center + p - o
+    }
+}
@@ -30,4 +30,16 @@ impl<H: Hittable> Hittable for Translate<H> {
             b
         })
     }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        self.hitable.pdf_value(&(o - &self.offset), v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        self.hitable.random(&(o - &self.offset))
+    }
+
+    fn sampling_tolerance(&self) -> f64 {
+        self.hitable.sampling_tolerance()
+    }
 }
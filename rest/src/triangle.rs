@@ -0,0 +1,152 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler;
+use crate::vec3::*;
+use rand::Rng;
+
+// Single triangle. Without per-vertex texture coordinates, uv are the
+// barycentric weights of `p1` and `p2`, so dpdu and dpdv are the two edges
+// from `p0`.
+#[derive(Clone)]
+pub struct Triangle<M: Material> {
+    p0: Point3,
+    e1: Vec3,
+    e2: Vec3,
+    normal: Vec3,
+    area: f64,
+    uvs: [(f64, f64); 3],
+    // Per-vertex shading normals, interpolated across the face.
+    normals: Option<[Vec3; 3]>,
+    material: M,
+}
+
+impl<M: Material> Triangle<M> {
+    #[allow(dead_code)]
+    pub fn new(p0: Point3, p1: Point3, p2: Point3, material: M) -> Self {
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+        let n = cross(&e1, &e2);
+        Triangle {
+            p0,
+            e1,
+            e2,
+            normal: unit_vector(&n),
+            area: 0.5 * n.length(),
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            normals: None,
+            material,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = uvs;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    // Position derivatives with respect to the vertex texture coordinates.
+    fn tangents(&self) -> (Vec3, Vec3) {
+        let [(u0, v0), (u1, v1), (u2, v2)] = self.uvs;
+        let (du1, dv1, du2, dv2) = (u1 - u0, v1 - v0, u2 - u0, v2 - v0);
+        let det = du1 * dv2 - dv1 * du2;
+        if det.abs() < 1e-12 {
+            return (Vec3::default(), Vec3::default());
+        }
+        let dpdu = (dv2 * self.e1 - dv1 * self.e2) / det;
+        let dpdv = (du1 * self.e2 - du2 * self.e1) / det;
+        (dpdu, dpdv)
+    }
+}
+
+impl<M: Material> Hittable for Triangle<M> {
+    // Moller-Trumbore intersection.
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let pvec = cross(ray.direction(), &self.e2);
+        let det = dot(&self.e1, &pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let tvec = ray.origin() - &self.p0;
+        let u = dot(&tvec, &pvec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let qvec = cross(&tvec, &self.e1);
+        let v = dot(ray.direction(), &qvec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = dot(&self.e2, &qvec) * inv_det;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let w = 1.0 - u - v;
+        let [(u0, v0), (u1, v1), (u2, v2)] = self.uvs;
+        let (dpdu, dpdv) = self.tangents();
+        let mut rec = HitRecord::new(
+            ray.at(t),
+            &self.material,
+            t,
+            w * u0 + u * u1 + v * u2,
+            w * v0 + u * v1 + v * v2,
+            ray,
+            self.normal,
+        )
+        .with_tangents(dpdu, dpdv);
+        rec.barycentrics = Some((u, v));
+        if let Some([n0, n1, n2]) = self.normals {
+            // Keep the shading normal on the same side as the geometric one.
+            let n = unit_vector(&(w * n0 + u * n1 + v * n2));
+            rec.shading_normal = if dot(&n, &rec.normal) < 0.0 { -n } else { n };
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let p1 = self.p0 + self.e1;
+        let p2 = self.p0 + self.e2;
+        let mut min = self.p0;
+        let mut max = self.p0;
+        for a in 0..3 {
+            min[a] = min[a].min(p1[a]).min(p2[a]);
+            max[a] = max[a].max(p1[a]).max(p2[a]);
+            if max[a] - min[a] < 0.0002 {
+                min[a] -= 0.0001;
+                max[a] += 0.0001;
+            }
+        }
+        Some(AABB::new(min, max))
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        if let Some(hit) = self.hit(&Ray::new(*o, *v, 0.0), 0.001, f64::MAX) {
+            let distance_squared = hit.t.powi(2) * v.length_squared();
+            let cosine = dot(v, &self.normal).abs() / v.length();
+            if cosine > 1e-8 {
+                distance_squared / (cosine * self.area)
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        let mut rng = sampler::rng();
+        let su = rng.gen::<f64>().sqrt();
+        let b1 = 1.0 - su;
+        let b2 = rng.gen::<f64>() * su;
+        let p = self.p0 + b1 * self.e1 + b2 * self.e2;
+        p - o
+    }
+}
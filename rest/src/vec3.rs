@@ -1,3 +1,4 @@
+use crate::sampler;
 use rand::Rng;
 
 use std::f64::consts::PI;
@@ -54,12 +55,12 @@ impl Vec3 {
     }
 
     pub fn random() -> Vec3 {
-        let mut rng = rand::thread_rng();
+        let mut rng = sampler::rng();
         Vec3(rng.gen(), rng.gen(), rng.gen())
     }
 
     pub fn random_minmax(min: f64, max: f64) -> Vec3 {
-        let mut rng = rand::thread_rng();
+        let mut rng = sampler::rng();
         Vec3(
             rng.gen_range(min..max),
             rng.gen_range(min..max),
@@ -308,7 +309,7 @@ pub fn unit_vector(v: &Vec3) -> Vec3 {
 
 #[allow(dead_code)]
 pub fn random_unit_vector() -> Vec3 {
-    let mut rng = rand::thread_rng();
+    let mut rng = sampler::rng();
     let phi: f64 = rng.gen_range(0.0..2.0 * PI);
     let z: f64 = rng.gen_range(-1.0..=1.0);
     let rz = f64::powf(1.0, 1. / 3.);
@@ -317,7 +318,7 @@ pub fn random_unit_vector() -> Vec3 {
 }
 
 pub fn random_in_unit_sphere() -> Vec3 {
-    let mut rng = rand::thread_rng();
+    let mut rng = sampler::rng();
     let phi: f64 = rng.gen_range(0.0..2.0 * PI);
     let z: f64 = rng.gen_range(-1.0..=1.0);
     let r: f64 = rng.gen_range(0.0..=1.0);
@@ -350,9 +351,13 @@ pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
 }
 
 pub fn random_in_unit_disk() -> Vec3 {
-    let mut rng = rand::thread_rng();
+    let mut rng = sampler::rng();
     let phi: f64 = rng.gen_range(0.0..2.0 * PI);
     let r: f64 = rng.gen_range(0.0..=1.0);
     let rxy = r.sqrt();
     return Vec3::new(rxy * phi.cos(), rxy * phi.sin(), 0.0);
 }
+
+pub fn lerp(a: &Vec3, b: &Vec3, t: f64) -> Vec3 {
+    (1.0 - t) * a + t * b
+}
@@ -0,0 +1,63 @@
+use crate::perlin::perlin_generate_perm;
+use crate::vec3::*;
+use rand::Rng;
+
+fn worley_generate() -> Vec<Vec3> {
+    let mut rng = rand::thread_rng();
+    let mut p = Vec::with_capacity(256);
+    for _ in 0..256 {
+        p.push(Vec3::new(rng.gen(), rng.gen(), rng.gen()));
+    }
+    p
+}
+
+// Cellular (Worley) noise: one jittered feature point per unit cell.
+#[derive(Clone)]
+pub struct Worley {
+    points: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Worley {
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Worley {
+            points: worley_generate(),
+            perm_x: perlin_generate_perm(),
+            perm_y: perlin_generate_perm(),
+            perm_z: perlin_generate_perm(),
+        }
+    }
+
+    fn feature_point(&self, i: i64, j: i64, k: i64) -> Vec3 {
+        let jitter = self.points[self.perm_x[(i & 255) as usize]
+            ^ self.perm_y[(j & 255) as usize]
+            ^ self.perm_z[(k & 255) as usize]];
+        Vec3::new(i as f64, j as f64, k as f64) + jitter
+    }
+
+    // Distances to the nearest and second nearest feature points (F1, F2).
+    pub fn distances(&self, p: &Vec3) -> (f64, f64) {
+        let i = p.x().floor() as i64;
+        let j = p.y().floor() as i64;
+        let k = p.z().floor() as i64;
+        let mut f1 = f64::MAX;
+        let mut f2 = f64::MAX;
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let d = (self.feature_point(i + di, j + dj, k + dk) - p).length();
+                    if d < f1 {
+                        f2 = f1;
+                        f1 = d;
+                    } else if d < f2 {
+                        f2 = d;
+                    }
+                }
+            }
+        }
+        (f1, f2)
+    }
+}